@@ -1,36 +1,101 @@
+use crate::bookmarks::{Bookmark, Bookmarks};
+use crate::config::{CLEAR_QUEUE_ALT, Config};
+use crate::eq::{BANDS, EqPresets, SavedEq};
 use crate::event::{AppEvent, Event, EventHandler};
+use crate::library::{LibraryCache, LibraryUpdate, Scan, ScanFailure, SourceRules};
+use crate::player::{Player, Shuffle};
+use crate::state::{Session, State};
+use crate::watch::SourceWatcher;
 use crate::widgets::{PopupManual, PopupNotif};
 use crossterm::event::KeyEventKind;
 use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
-use lofty::file::AudioFile;
-use lofty::prelude::*;
-use lofty::probe::Probe;
 use ratatui::{
     DefaultTerminal,
-    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    layout::Rect,
     style::Color,
 };
-use rodio::{OutputStream, Sink};
-use std::io::BufReader;
-use std::path::Path;
-use std::time::Duration;
-use walkdir::WalkDir;
-
-const VOLUME_CHANGE: f32 = 0.05;
-const NOTIF_DURATION: usize = 60;
-const SEEK_CHANGE: Duration = Duration::from_secs(5);
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often the session is saved while running, so a crash doesn't lose it.
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+/// How long the library has to stay unchanged before it's saved, so a burst of changes on disk
+/// is written once.
+const LIBRARY_SAVE_DELAY: Duration = Duration::from_secs(10);
+/// Minutes the sleep timer key steps through.
+const SLEEP_MINUTES: [u64; 5] = [15, 30, 45, 60, 90];
+
 /// Application.
-#[derive(Clone)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Song {
     pub file_path: String,
     pub file_type: String,
     pub file_name: String,
     pub is_valid: bool,
-    pub title: String,
-    pub artist: String,
-    pub album: String,
-    pub genre: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub composer: Option<String>,
+    pub comment: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub year: Option<u32>,
     pub duration: Duration,
+    /// Audio bitrate in kbps.
+    pub bitrate: Option<u32>,
+    /// Sample rate in Hz.
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+    pub bit_depth: Option<u8>,
+    /// Priming frames the encoder put before the audio.
+    pub encoder_delay: Option<u32>,
+    /// Frames of actual audio after the delay, whatever follows is encoder padding.
+    pub audio_frames: Option<u64>,
+    /// ReplayGain adjustments in dB, R128 tags are converted to the ReplayGain reference level.
+    pub track_gain: Option<f32>,
+    pub album_gain: Option<f32>,
+    /// Loudest sample, 1 being full scale.
+    pub track_peak: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl Song {
+    /// The title tag, or the file name for untagged files.
+    pub fn display_title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.file_name)
+    }
+
+    /// Identifies the album the song belongs to.
+    ///
+    /// The album name alone isn't enough, plenty of artists have a "Greatest Hits". Albums are
+    /// told apart by album artist, or by directory for files that don't have one.
+    pub fn album_key(&self) -> (Option<&str>, String) {
+        let owner = match &self.album_artist {
+            Some(album_artist) => album_artist.clone(),
+            None => Path::new(&self.file_path)
+                .parent()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+        };
+        (self.album.as_deref(), owner)
+    }
+
+    /// Sort key for playing an album in order: by disc, then track number. Untagged tracks go
+    /// last, ties are broken by path.
+    pub fn track_order(&self) -> (u32, u32, &str) {
+        (
+            self.disc_number.unwrap_or(1),
+            self.track_number.unwrap_or(u32::MAX),
+            &self.file_path,
+        )
+    }
 }
 pub struct App {
     /// Is the application running?
@@ -38,20 +103,60 @@ pub struct App {
 
     pub search_results: Vec<Song>,
     pub search_cache: Vec<Song>,
-    pub queue: Vec<Song>,
+    pub library: LibraryCache,
+    pub scan: Option<Scan>,
+    /// Files skipped by the last scan, shown in the scan report.
+    pub scan_failures: Vec<ScanFailure>,
+    pub watcher: Option<SourceWatcher>,
+    pub player: Player,
     pub query: String,
     pub select_index: usize,
+    /// Position of the cursor over the queue in sitback mode.
+    pub queue_index: usize,
+    /// Files listed in the playlist browser.
+    pub playlists: Vec<PathBuf>,
+    pub playlist_index: usize,
+    /// Name typed in for saving the queue as a playlist.
+    pub playlist_name: String,
+    /// Band selected in the equalizer.
+    pub eq_band: usize,
+    pub bookmarks: Bookmarks,
+    pub bookmark_index: usize,
+    /// Name typed in for a new bookmark, and the position it will point to.
+    pub bookmark_name: String,
+    pub bookmark_at: Duration,
+    /// Typed in command line, without the `:`.
+    pub command: String,
+    pub sleep: Option<Sleep>,
+    /// Volume from before the sleep timer started fading it out.
+    sleep_volume: Option<f32>,
     pub sources: Option<Vec<(String, bool)>>,
+    /// Rules of the sources that exist, used for scanning and watching.
+    pub source_rules: Vec<SourceRules>,
+    pub config: Config,
+    /// Problems found while loading the config or watching the sources, shown in the help desk.
+    pub config_errors: Vec<String>,
     /// Event handler.
     pub events: EventHandler,
     pub mode: Mode,
-    pub sink: Sink,
-    pub stream: OutputStream,
     pub terminal_size: (u16, u16),
     pub search_by: SearchBy,
     pub popup_manual: Option<PopupManual>,
     pub popup_notif: Vec<PopupNotif>,
-    // pub stream_handle: OutputStreamHandle,
+    /// When the state was last written to disk.
+    pub state_saved: Instant,
+    /// Whether the last state save failed, so autosaves only report it once.
+    state_save_failed: bool,
+    /// When the library was last changed, if that wasn't saved yet.
+    library_changed: Option<Instant>,
+}
+
+/// What the sleep timer waits for before pausing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sleep {
+    At(Instant),
+    /// The player counts the songs, see [`Player::stop_after`].
+    Songs,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -59,9 +164,22 @@ pub enum Mode {
     Sitback,
     Search,
     Select,
+    History,
+    /// Browsing the playlists directory.
+    Playlists,
+    /// Typing in a name for saving the queue.
+    SavePlaylist,
+    /// Adjusting the equalizer.
+    Equalizer,
+    /// Browsing the bookmarks of the current song.
+    Bookmarks,
+    /// Typing in a name for a bookmark.
+    NameBookmark,
+    /// Typing in a command after `:`.
+    Command,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SearchBy {
     FilePath,
     Title,
@@ -73,172 +191,596 @@ pub enum SearchBy {
 impl Default for App {
     fn default() -> Self {
         // Constructs a new instance of [`App`].
-        let (stream, stream_handle) = OutputStream::try_default().unwrap();
-        let sources = App::check_config_validity();
+        let (config, mut config_errors) = Config::load();
+        let sources = App::check_config_validity(&config);
+        let library = LibraryCache::load();
+        let events = EventHandler::new(config.ui.tick_fps);
+        let source_rules = App::source_rules(&config);
+        let watcher = match SourceWatcher::new(&source_rules, events.sender()) {
+            Ok((watcher, failures)) => {
+                for (root, err) in failures {
+                    config_errors.push(format!(
+                        "Couldn't watch {} for changes: {err}",
+                        root.display()
+                    ));
+                }
+                Some(watcher)
+            }
+            Err(err) => {
+                config_errors.push(format!("Couldn't watch the sources for changes: {err}"));
+                None
+            }
+        };
+        let state = State::load();
+        let mut player = Player::new().expect("Couldn't open the audio output");
+        player.set_repeat(state.repeat);
+        player.set_album_mode(config.playback.album_mode);
+        player.set_replaygain(config.playback.replaygain);
+        let mut eq = EqPresets::new(&config.eq);
+        eq.restore(SavedEq::load());
+        player.set_eq(eq);
+        if config.playback.crossfade {
+            player.set_crossfade(Some(Duration::from_secs_f64(
+                config.playback.crossfade_seconds,
+            )));
+        }
+        let mut search_by = SearchBy::FilePath;
+        if config.playback.restore_session
+            && let Some(session) = state.session
+        {
+            player.set_volume(session.volume);
+            player.restore(session.queue, session.position);
+            search_by = session.search_by;
+        }
 
         let mut init = Self {
             running: true,
             search_results: Vec::new(),
-            search_cache: Vec::new(),
-            queue: Vec::new(),
+            search_cache: library.songs(),
+            library,
+            scan: None,
+            scan_failures: Vec::new(),
+            watcher,
+            player,
             query: String::new(),
-            events: EventHandler::default(),
+            events,
             mode: Mode::Sitback,
             select_index: 0,
+            queue_index: 0,
+            playlists: Vec::new(),
+            playlist_index: 0,
+            playlist_name: String::new(),
+            eq_band: 0,
+            bookmarks: Bookmarks::load(),
+            bookmark_index: 0,
+            bookmark_name: String::new(),
+            bookmark_at: Duration::ZERO,
+            command: String::new(),
+            sleep: None,
+            sleep_volume: None,
             sources,
-            sink: Sink::try_new(&stream_handle).unwrap(),
-            stream: stream,
+            source_rules,
+            config,
+            config_errors,
             terminal_size: (0, 0),
-            search_by: SearchBy::FilePath,
+            search_by,
             popup_manual: None,
             popup_notif: Vec::new(),
+            state_saved: Instant::now(),
+            state_save_failed: false,
+            library_changed: None,
         };
-        init.events.send(AppEvent::InitPopup);
         init.events.send(AppEvent::RefreshCache);
         init.events.send(AppEvent::HelpDesk);
-        return init;
+        init
     }
 }
 
 impl App {
-    pub fn config_check_file_exists() -> bool {
-        if let Some(cfg_dir) = dirs::config_dir() {
-            let exists = cfg_dir.join("rrplay").join("config.txt");
-            if !exists.is_file() {
-                let mut config_file = cfg_dir.join("rrplay");
-                std::fs::create_dir_all(config_file.clone()).unwrap();
-                config_file = cfg_dir.join("rrplay/config.txt");
-                std::fs::write(config_file, "").unwrap();
-                true
-            } else {
-                true
-            }
-        } else {
-            false
+    pub fn check_config_validity(config: &Config) -> Option<Vec<(String, bool)>> {
+        if config.sources.is_empty() {
+            return None;
         }
+        Some(
+            config
+                .sources
+                .iter()
+                .map(|source| (source.path.clone(), Path::new(&source.path).exists()))
+                .collect(),
+        )
     }
 
-    pub fn check_config_validity() -> Option<Vec<(String, bool)>> {
-        if App::config_check_file_exists() {
-            if let Some(cfg_dir) = dirs::config_dir() {
-                let config_file = cfg_dir.join("rrplay").join("config.txt");
-                let file_contents: Option<String> = match std::fs::read_to_string(config_file) {
-                    Ok(content) => {
-                        if content.is_empty() {
-                            None
-                        } else {
-                            Some(content)
-                        }
-                    }
-                    Err(_) => None,
-                };
-                match file_contents {
-                    Some(c) => {
-                        let paths = c.split("\n");
-                        let mut output: Vec<(String, bool)> = Vec::new();
-                        for path in paths {
-                            let ap = path.trim().to_string();
-
-                            if Path::new(&ap).exists() {
-                                output.push((ap, true));
-                            } else {
-                                output.push((ap, false));
-                            }
-                        }
-                        return Some(output);
-                    }
-                    None => {
-                        return None;
+    pub fn source_rules(config: &Config) -> Vec<SourceRules> {
+        config
+            .sources
+            .iter()
+            .filter(|source| Path::new(&source.path).exists())
+            .map(SourceRules::new)
+            .collect()
+    }
+
+    /// Fuzzy matches a song against the query, using whichever field is being searched by.
+    fn match_score(&self, matcher: &SkimMatcherV2, song: &Song) -> Option<i64> {
+        matcher
+            .fuzzy_match(
+                match self.search_by {
+                    SearchBy::FilePath => song.file_path.as_str(),
+                    SearchBy::Title => song.display_title(),
+                    SearchBy::Artist => song.artist.as_deref().unwrap_or_default(),
+                    SearchBy::Album => song.album.as_deref().unwrap_or_default(),
+                    SearchBy::Genre => song.genre.as_deref().unwrap_or_default(),
+                },
+                self.query.as_str(),
+            )
+            .filter(|score| *score > 0)
+    }
+
+    /// Merges files that changed on disk into the library and the current search results.
+    ///
+    /// Search results are updated in place rather than searched again, so the selection stays
+    /// on the same song.
+    fn apply_library_update(&mut self, update: LibraryUpdate) {
+        let is_removed_path = |path: &str| {
+            update
+                .removed
+                .iter()
+                .any(|r| Path::new(path).starts_with(r))
+        };
+        let is_removed = |song: &Song| is_removed_path(&song.file_path);
+        let selected = self
+            .search_results
+            .len()
+            .checked_sub(1 + self.select_index)
+            .map(|i| self.search_results[i].file_path.clone());
+
+        // Touched paths get a fresh verdict, whether they failed before or not
+        let failed: HashSet<&str> = update.failures.iter().map(|f| f.path.as_str()).collect();
+        let touched: HashSet<&str> = update
+            .added
+            .iter()
+            .map(|e| e.song.file_path.as_str())
+            .chain(failed.iter().copied())
+            .collect();
+        self.scan_failures
+            .retain(|f| !is_removed_path(&f.path) && !touched.contains(f.path.as_str()));
+        self.scan_failures.extend(update.failures.iter().cloned());
+        // Files that can't be read anymore leave the library, like they would after a scan
+        let is_removed = |song: &Song| is_removed(song) || failed.contains(song.file_path.as_str());
+
+        let before = self.library.entries.len();
+        self.library.entries.retain(|e| !is_removed(&e.song));
+        let removed = before - self.library.entries.len();
+        self.search_cache.retain(|s| !is_removed(s));
+        self.search_results.retain(|s| !is_removed(s));
+
+        // Looked up by path once, a big batch would otherwise search the library for every file
+        let mut library_index = index_by_path(self.library.entries.iter().map(|e| &e.song));
+        let mut cache_index = index_by_path(self.search_cache.iter());
+        let results_index = index_by_path(self.search_results.iter());
+        let matcher = SkimMatcherV2::default();
+        let (mut added, mut changed) = (0, 0);
+        for entry in update.added {
+            let song = entry.song.clone();
+            match library_index.get(&song.file_path) {
+                Some(&i) => {
+                    self.library.entries[i] = entry;
+                    changed += 1;
+                }
+                None => {
+                    library_index.insert(song.file_path.clone(), self.library.entries.len());
+                    self.library.entries.push(entry);
+                    added += 1;
+                }
+            }
+            match cache_index.get(&song.file_path) {
+                Some(&i) => self.search_cache[i] = song.clone(),
+                None => {
+                    cache_index.insert(song.file_path.clone(), self.search_cache.len());
+                    self.search_cache.push(song.clone());
+                }
+            }
+            match results_index.get(&song.file_path) {
+                Some(&i) => self.search_results[i] = song,
+                None => {
+                    if !self.query.is_empty() && self.match_score(&matcher, &song).is_some() {
+                        self.search_results.push(song);
                     }
                 }
+            }
+        }
+
+        self.select_index = selected
+            .and_then(|path| self.search_results.iter().position(|s| s.file_path == path))
+            .map_or(0, |i| self.search_results.len() - 1 - i);
+        if self.mode == Mode::Select && self.search_results.is_empty() {
+            self.mode = Mode::Search;
+        }
+        self.library_changed = Some(Instant::now());
+
+        if added + changed + removed > 0 {
+            let mut message = vec![("Library updated".to_string(), Color::White)];
+            if added > 0 {
+                message.push((format!("+{added} added"), Color::Green));
+            }
+            if changed > 0 {
+                message.push((format!("~{changed} changed"), Color::Yellow));
+            }
+            if removed > 0 {
+                message.push((format!("-{removed} removed"), Color::Red));
+            }
+            self.popup_notif.push(PopupNotif {
+                message,
+                border_color: Color::Blue,
+                duration_ticks: Some(self.config.ui.notif_duration),
+                title: "".to_string(),
+                index: self.popup_notif.len() + 1,
+            });
+        }
+    }
+
+    /// The selected search result, if its file is still there.
+    fn selected_song(&mut self) -> Vec<Song> {
+        if self.search_results.is_empty() {
+            return Vec::new();
+        }
+        let index = self.search_results.len() - 1 - self.select_index;
+        let song = &mut self.search_results[index];
+        if Path::new(&song.file_path).is_file() {
+            vec![song.clone()]
+        } else {
+            song.is_valid = false;
+            Vec::new()
+        }
+    }
+
+    /// Every song of the selected search result's album whose file is still there, in track
+    /// order.
+    fn selected_album(&mut self) -> Vec<Song> {
+        if self.search_results.is_empty() {
+            return Vec::new();
+        }
+        let index = self.search_results.len() - 1 - self.select_index;
+        let album = self.search_results[index].album_key();
+
+        let mut tracks: Vec<usize> = (0..self.search_cache.len())
+            .filter(|&i| self.search_cache[i].album_key() == album)
+            .collect();
+        tracks.sort_by(|&a, &b| {
+            self.search_cache[a]
+                .track_order()
+                .cmp(&self.search_cache[b].track_order())
+        });
+
+        let mut songs = Vec::new();
+        for i in tracks {
+            let song = &mut self.search_cache[i];
+            if Path::new(&song.file_path).is_file() {
+                songs.push(song.clone());
             } else {
-                None
+                song.is_valid = false;
             }
+        }
+        songs
+    }
+
+    fn notify(&mut self, message: &str, border_color: Color) {
+        self.popup_notif.push(PopupNotif {
+            message: vec![(message.to_string(), Color::White)],
+            border_color,
+            duration_ticks: Some(self.config.ui.notif_duration),
+            title: "".to_string(),
+            index: self.popup_notif.len() + 1,
+        });
+    }
+
+    /// Bottom hint of the popups that scroll, naming the configured keys.
+    fn scroll_hint(&self) -> String {
+        let keys = &self.config.keys;
+        format!(
+            "Press <Esc> to close the window, {}/{} to scroll",
+            keys.move_down, keys.move_up
+        )
+    }
+
+    /// Adds a playlist to the queue. Songs are taken from the library when possible, the rest
+    /// are read in the background and arrive as [`AppEvent::PlaylistLoaded`].
+    fn load_playlist(&mut self, path: &Path) {
+        let entries = match crate::playlist::load(path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                self.notify(
+                    &format!("Couldn't read {}: {err}", path.display()),
+                    Color::Red,
+                );
+                return;
+            }
+        };
+        let known: HashMap<&str, &Song> = self
+            .search_cache
+            .iter()
+            .map(|s| (s.file_path.as_str(), s))
+            .collect();
+        let listed = entries
+            .into_iter()
+            .map(
+                |entry| match known.get(entry.path.display().to_string().as_str()) {
+                    Some(song) => Ok((*song).clone()),
+                    None => Err(entry),
+                },
+            )
+            .collect();
+        let name = path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let sender = self.events.sender();
+        tokio::task::spawn_blocking(move || crate::playlist::open_songs(name, listed, sender));
+    }
+
+    /// Queues the songs of a playlist once they were read.
+    fn add_playlist(&mut self, name: String, songs: Vec<Song>) {
+        let missing = songs.iter().filter(|s| !s.is_valid).count();
+        let count = songs.len();
+        self.player.push(songs);
+        self.player.play();
+        self.mode = Mode::Sitback;
+
+        if missing > 0 {
+            self.notify(
+                &format!("Loaded {name}, {missing} of {count} songs are missing"),
+                Color::Yellow,
+            );
         } else {
-            None
-        }
-    }
-    pub fn search_directories(check_sources: Option<Vec<(String, bool)>>) -> Vec<Song> {
-        if let Some(sources) = check_sources {
-            let mut out = Vec::new();
-            let source_filter: Vec<(String, bool)> = sources.into_iter().filter(|x| x.1).collect();
-            for source in source_filter {
-                let file_types = [
-                    "flac", "m4a", "mp3", "wav", "ogg", "opus", "m4p", "aiff", "3gp", "aac",
-                ];
-
-                for entry in WalkDir::new(source.0).into_iter().filter_map(|e| e.ok()) {
-                    if let Some(ext) = entry.path().extension() {
-                        if file_types.contains(&ext.to_str().unwrap_or("No ext")) {
-                            if let Some(filename) =
-                                Path::new(entry.clone().path().as_os_str()).file_name()
-                            {
-                                let tagged_file = Probe::open(entry.clone().path())
-                                    .expect("ERROR: Bad path provided!")
-                                    .read()
-                                    .expect("ERROR: Failed to read file!");
-                                let duration: Duration = tagged_file.properties().duration();
-                                match tagged_file.primary_tag() {
-                                    Some(primary_tag) => {
-                                        let new_entry: Song = Song {
-                                            file_path: entry.path().display().to_string(),
-                                            file_name: filename.to_str().unwrap().to_string(),
-                                            file_type: ext.to_str().unwrap().to_string(),
-                                            is_valid: true,
-                                            title: primary_tag
-                                                .title()
-                                                .as_deref()
-                                                .unwrap_or("N/A")
-                                                .to_string(),
-                                            album: {
-                                                primary_tag
-                                                    .album()
-                                                    .as_deref()
-                                                    .unwrap_or("N/A")
-                                                    .to_string()
-                                            },
-                                            artist: primary_tag
-                                                .artist()
-                                                .as_deref()
-                                                .unwrap_or("N/A")
-                                                .to_string(),
-                                            duration,
-                                            genre: primary_tag
-                                                .genre()
-                                                .as_deref()
-                                                .unwrap_or("N/A")
-                                                .to_string(),
-                                        };
-                                        out.push(new_entry);
-                                    }
+            self.notify(&format!("Loaded {name} ({count} songs)"), Color::Green);
+        }
+    }
 
-                                    // If the "primary" tag doesn't exist, we just grab the
-                                    // first tag we can find. Realistically, a tag reader would likely
-                                    // iterate through the tags to find a suitable one.
-                                    _ => {
-                                        let new_entry: Song = Song {
-                                            file_path: entry.path().display().to_string(),
-                                            file_name: filename.to_str().unwrap().to_string(),
-                                            file_type: ext.to_str().unwrap().to_string(),
-                                            is_valid: true,
-                                            title: filename.to_str().unwrap().to_string(),
-                                            artist: "N/A".to_string(),
-                                            album: "N/A".to_string(),
-                                            duration,
-                                            genre: "N/A".to_string(),
-                                        };
-                                        out.push(new_entry);
-                                    }
-                                };
-                            }
-                        }
-                    }
+    /// Remembers the playback settings, and the session if enabled, for the next run.
+    fn save_state(&mut self) {
+        let session = self.config.playback.restore_session.then(|| Session {
+            queue: self.player.queue.clone(),
+            position: self.player.position(),
+            volume: self.base_volume(),
+            search_by: self.search_by,
+        });
+        let state = State {
+            repeat: self.player.repeat(),
+            session,
+        };
+        match state.save() {
+            Ok(()) => self.state_save_failed = false,
+            Err(err) => {
+                if !self.state_save_failed {
+                    self.notify(&format!("Couldn't save the state: {err}"), Color::Red);
                 }
+                self.state_save_failed = true;
             }
-            out
-        } else {
-            Vec::new()
         }
+        self.state_saved = Instant::now();
+    }
+
+    /// Whether the bottom bar shows the progress of the current song.
+    pub fn shows_gauge(&self) -> bool {
+        matches!(
+            self.mode,
+            Mode::Sitback | Mode::History | Mode::Playlists | Mode::Equalizer | Mode::Bookmarks
+        )
+    }
+
+    /// Runs a command typed after `:`, e.g. `seek 2:31`.
+    fn run_command(&mut self) {
+        let command = std::mem::take(&mut self.command);
+        let (name, argument) = command
+            .trim()
+            .split_once(' ')
+            .unwrap_or((command.trim(), ""));
+        match name {
+            "" => {}
+            "q" | "quit" => self.events.send(AppEvent::Quit),
+            "help" => self.events.send(AppEvent::HelpDesk),
+            "seek" => {
+                let Some(song) = self.player.queue.first() else {
+                    self.notify("Nothing is playing", Color::Yellow);
+                    return;
+                };
+                match parse_seek(argument.trim(), self.player.position(), song.duration) {
+                    Some(position) => self.events.send(AppEvent::SeekTo(position)),
+                    None => self.notify(
+                        "Seek to a time (2:31), by seconds (+30, -1:00) or a percentage (50%)",
+                        Color::Red,
+                    ),
+                }
+            }
+            "sleep" => {
+                let argument = argument.trim();
+                let songs = argument
+                    .trim_end_matches('s')
+                    .strip_suffix("song")
+                    .or_else(|| argument.trim_end_matches('s').strip_suffix("track"))
+                    .and_then(|count| count.trim().parse::<usize>().ok())
+                    .filter(|count| *count > 0);
+                let until = argument
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|minutes| *minutes > 0.0)
+                    .and_then(|minutes| Duration::try_from_secs_f64(minutes * 60.0).ok())
+                    .and_then(|time| Instant::now().checked_add(time));
+                if argument == "off" {
+                    self.set_sleep(None);
+                } else if let Some(songs) = songs {
+                    self.set_sleep(Some(Sleep::Songs));
+                    self.player.set_stop_after(Some(songs));
+                } else if let Some(until) = until {
+                    self.set_sleep(Some(Sleep::At(until)));
+                } else {
+                    self.notify(
+                        "Sleep after minutes (30), songs (3 songs) or turn it off (off)",
+                        Color::Red,
+                    );
+                }
+            }
+            _ => self.notify(&format!("Unknown command: {name}"), Color::Red),
+        }
+    }
+
+    /// How long until the sleep timer pauses. With a number of songs, that's only known once
+    /// the last one is playing.
+    pub fn sleep_remaining(&self) -> Option<Duration> {
+        match self.sleep? {
+            Sleep::At(until) => Some(until.saturating_duration_since(Instant::now())),
+            Sleep::Songs if self.player.stop_after() == Some(1) => {
+                let song = self.player.queue.first()?;
+                Some(
+                    song.duration
+                        .saturating_sub(self.player.position())
+                        .div_f32(self.player.speed()),
+                )
+            }
+            Sleep::Songs => None,
+        }
+    }
+
+    /// Replaces the sleep timer, bringing the volume back if it was fading out.
+    fn set_sleep(&mut self, sleep: Option<Sleep>) {
+        if let Some(volume) = self.sleep_volume.take() {
+            self.player.set_volume(volume);
+        }
+        if self.sleep == Some(Sleep::Songs) {
+            self.player.set_stop_after(None);
+        }
+        self.sleep = sleep;
+    }
+
+    /// Pauses once the sleep timer runs out, fading the volume out before that.
+    fn update_sleep(&mut self) {
+        let Some(sleep) = self.sleep else {
+            return;
+        };
+        let ran_out = match sleep {
+            Sleep::At(until) => Instant::now() >= until,
+            // The player pauses by itself after the last song
+            Sleep::Songs => self.player.stop_after().is_none(),
+        };
+        if ran_out {
+            self.player.pause();
+            self.set_sleep(None);
+            self.notify("Sleep timer ran out, good night", Color::Green);
+            return;
+        }
+        let fade = self.config.playback.sleep_fade_seconds as f32;
+        if let Some(remaining) = self.sleep_remaining()
+            && remaining.as_secs_f32() < fade
+        {
+            let volume = *self.sleep_volume.get_or_insert(self.player.volume());
+            self.player
+                .set_volume(volume * remaining.as_secs_f32() / fade);
+        }
+    }
+
+    /// The volume as the user set it, which the sleep timer may be fading out from.
+    fn base_volume(&self) -> f32 {
+        self.sleep_volume.unwrap_or(self.player.volume())
+    }
+
+    fn set_base_volume(&mut self, volume: f32) {
+        match &mut self.sleep_volume {
+            Some(sleep_volume) => *sleep_volume = volume,
+            None => self.player.set_volume(volume),
+        }
+    }
+
+    /// The bookmarks of the song that is playing.
+    pub fn current_bookmarks(&self) -> &[Bookmark] {
+        match self.player.queue.first() {
+            Some(song) => self.bookmarks.get(&song.file_path),
+            None => &[],
+        }
+    }
+
+    /// Writes the library cache on a blocking thread, a big library takes a while to write.
+    /// Failures come back as [`AppEvent::LibrarySaveFailed`].
+    fn save_library(&mut self) {
+        self.library_changed = None;
+        let library = self.library.clone();
+        let sender = self.events.sender();
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = library.save() {
+                let _ = sender.send(Event::App(AppEvent::LibrarySaveFailed(err.to_string())));
+            }
+        });
+    }
+
+    fn save_bookmarks(&mut self) {
+        if let Err(err) = self.bookmarks.save() {
+            self.notify(&format!("Couldn't save the bookmarks: {err}"), Color::Red);
+        }
+    }
+
+    fn change_speed(&mut self, speed: f32) {
+        // Rounded so repeated steps don't drift away from round numbers
+        let speed = (speed * 100.0).round() / 100.0;
+        self.player
+            .set_speed(speed, self.config.playback.keep_pitch);
+    }
+
+    /// Writes equalizer presets edited in the panel, and the one picked, to their own file.
+    fn save_equalizer(&mut self) {
+        let Some(saved) = self.player.save_eq() else {
+            return;
+        };
+        match saved.save() {
+            Ok(()) => self.notify("Saved the equalizer presets", Color::Green),
+            Err(err) => self.notify(
+                &format!("Couldn't save the equalizer presets: {err}"),
+                Color::Red,
+            ),
+        }
+    }
+
+    /// Flags a song that couldn't be played wherever it's listed.
+    fn mark_invalid(&mut self, song: &Song) {
+        for s in self
+            .search_cache
+            .iter_mut()
+            .chain(self.search_results.iter_mut())
+        {
+            if s.file_path == song.file_path {
+                s.is_valid = false;
+            }
+        }
+        self.popup_notif.push(PopupNotif {
+            message: vec![(
+                format!("Couldn't play {}", song.display_title()),
+                Color::White,
+            )],
+            border_color: Color::Red,
+            duration_ticks: Some(self.config.ui.notif_duration),
+            title: "".to_string(),
+            index: self.popup_notif.len() + 1,
+        });
+    }
+
+    /// Starts scanning the valid sources on a background thread.
+    ///
+    /// Results arrive as [`AppEvent::ScanBatch`]es. Until the scan finishes the previous library
+    /// stays searchable, or, if there was none, the songs found so far are.
+    pub fn start_scan(&mut self) {
+        if self.scan.is_some() {
+            return;
+        }
+        let sources = self.source_rules.clone();
+        self.scan = Some(Scan {
+            entries: Vec::new(),
+            failures: Vec::new(),
+            progress: Default::default(),
+            live: self.search_cache.is_empty(),
+        });
+        let previous = self.library.clone();
+        let sender = self.events.sender();
+        let threads = self.config.library.scan_threads;
+        tokio::task::spawn_blocking(move || {
+            crate::library::scan(sources, previous, threads, sender)
+        });
     }
     /// Run the application's main loop.
     pub async fn run(mut self, mut terminal: DefaultTerminal) -> color_eyre::Result<()> {
@@ -248,11 +790,13 @@ impl App {
             self.terminal_size = (size.width, size.height);
             match self.events.next().await? {
                 Event::Tick => self.tick(),
-                Event::Crossterm(event) => {
-                    if let crossterm::event::Event::Key(key_event) = event {
-                        self.handle_key_events(key_event)?
+                Event::Crossterm(event) => match event {
+                    crossterm::event::Event::Key(key_event) => self.handle_key_events(key_event)?,
+                    crossterm::event::Event::Mouse(mouse_event) => {
+                        self.handle_mouse_events(mouse_event)
                     }
-                }
+                    _ => {}
+                },
                 Event::App(app_event) => match app_event {
                     AppEvent::Quit => self.quit(),
                     AppEvent::Search => {
@@ -271,10 +815,49 @@ impl App {
                             if self.mode == Mode::Select {
                                 self.mode = Mode::Search;
                             } else {
+                                if self.mode == Mode::Equalizer {
+                                    self.save_equalizer();
+                                }
                                 self.mode = Mode::Sitback;
                             }
                         }
                     }
+                    AppEvent::MoveUp if self.mode == Mode::Equalizer => {
+                        self.player.adjust_eq(self.eq_band, 1.0);
+                    }
+                    AppEvent::MoveDown if self.mode == Mode::Equalizer => {
+                        self.player.adjust_eq(self.eq_band, -1.0);
+                    }
+                    AppEvent::MoveBackward if self.mode == Mode::Equalizer => {
+                        self.eq_band = self.eq_band.saturating_sub(1);
+                    }
+                    AppEvent::MoveForward if self.mode == Mode::Equalizer => {
+                        self.eq_band = (self.eq_band + 1).min(BANDS.len() - 1);
+                    }
+                    AppEvent::MoveUp if self.mode == Mode::Bookmarks => {
+                        self.bookmark_index = self.bookmark_index.saturating_sub(1);
+                    }
+                    AppEvent::MoveDown if self.mode == Mode::Bookmarks => {
+                        if self.bookmark_index + 1 < self.current_bookmarks().len() {
+                            self.bookmark_index += 1;
+                        }
+                    }
+                    AppEvent::MoveUp if self.mode == Mode::Playlists => {
+                        self.playlist_index = self.playlist_index.saturating_sub(1);
+                    }
+                    AppEvent::MoveDown if self.mode == Mode::Playlists => {
+                        if self.playlist_index + 1 < self.playlists.len() {
+                            self.playlist_index += 1;
+                        }
+                    }
+                    AppEvent::MoveUp if self.mode == Mode::Sitback => {
+                        self.queue_index = self.queue_index.saturating_sub(1);
+                    }
+                    AppEvent::MoveDown if self.mode == Mode::Sitback => {
+                        if self.queue_index + 1 < self.player.queue.len() {
+                            self.queue_index += 1;
+                        }
+                    }
                     AppEvent::MoveUp => {
                         if (self.select_index as i32) < (self.search_results.len() as i32 - 1)
                             && !self.search_results.is_empty()
@@ -291,16 +874,23 @@ impl App {
                         self.popup_manual = Some(PopupManual {
                             title: "Help desk".to_string(),
                             border_color: {
-                                if self.sources.is_some() {
+                                if self.sources.is_some() && self.config_errors.is_empty() {
                                     Color::White
                                 } else {
                                     Color::Red
                                 }
                             },
-                            bottom_hint: PopupManual::default().bottom_hint,
+                            bottom_hint: self.scroll_hint(),
                             message: {
+                                let mut out: Vec<(String, Color)> = Vec::new();
+                                if !self.config_errors.is_empty() {
+                                    out.push(("Errors:".to_string(), Color::White));
+                                    for e in &self.config_errors {
+                                        out.push((e.clone(), Color::Red));
+                                    }
+                                    out.push(("".to_string(), Color::White));
+                                }
                                 if let Some(sources_ok) = self.sources.clone() {
-                                    let mut out: Vec<(String, Color)> = Vec::new();
                                     out.push(("Sources:".to_string(), Color::White));
                                     for s in sources_ok {
                                         if s.1 {
@@ -309,160 +899,345 @@ impl App {
                                             out.push((s.0, Color::Red));
                                         }
                                     }
-                                    out
                                 } else {
-                                    let mut ret = vec![
-                                        "No sources found...".to_string(),
-                                        "Add some!".to_string(),
-                                        "File location:".to_string(),
-                                    ];
-                                    if let Some(cfg_dir) = dirs::config_dir() {
-                                        ret.push(
-                                            cfg_dir
-                                                .join("rrplay")
-                                                .join("config.txt")
-                                                .display()
-                                                .to_string(),
-                                        );
-                                    }
-                                    ret.clone()
-                                        .iter()
-                                        .map(|t| (t.to_string(), Color::White))
-                                        .collect()
+                                    out.push(("No sources found...".to_string(), Color::White));
+                                    out.push(("Add some!".to_string(), Color::White));
+                                }
+                                if let Some(config_file) = Config::config_file() {
+                                    out.push(("File location:".to_string(), Color::White));
+                                    out.push((config_file.display().to_string(), Color::White));
                                 }
+                                out
                             },
+                            scroll: 0,
                         });
                     }
-                    AppEvent::AddSingle => {
-                        if !self.search_results.is_empty() {
-                            let index = self.search_results.len() - 1 - self.select_index;
-
-                            let song = &self.search_results[index];
-
-                            if Path::new(&song.file_path).is_file() {
-                                let file = std::fs::File::open(song.clone().file_path).unwrap();
-
-                                if let Ok(decoder) = rodio::Decoder::new(BufReader::new(file)) {
-                                    self.sink.append(decoder);
-                                    self.queue.push(song.clone());
-                                } else {
-                                    self.search_results[index].is_valid = false;
-                                }
-                            } else {
-                                self.search_results[index].is_valid = false;
-                            }
+                    AppEvent::ScanReport => {
+                        let mut message = Vec::new();
+                        if self.scan_failures.is_empty() {
+                            message.push(("No files were skipped".to_string(), Color::White));
                         }
-                    }
-                    AppEvent::AddAlbum => {
-                        if !self.search_results.is_empty() {
-                            let index = self.search_results.len() - 1 - self.select_index;
-                            let song = self.search_results[index].clone();
-                            let album_name = song.album;
-
-                            for song in &mut self.search_cache {
-                                if song.album == album_name {
-                                    if Path::new(&song.file_path).is_file() {
-                                        let file =
-                                            std::fs::File::open(song.clone().file_path).unwrap();
-                                        if let Ok(decoder) =
-                                            rodio::Decoder::new(BufReader::new(file))
-                                        {
-                                            self.sink.append(decoder);
-                                            self.queue.push(song.clone());
-                                        } else {
-                                            song.is_valid = false;
-                                        }
-                                    } else {
-                                        song.is_valid = false;
-                                    }
-                                }
-                            }
+                        for failure in &self.scan_failures {
+                            message.push((failure.path.clone(), Color::Red));
+                            message.push((format!("  {}", failure.error), Color::White));
                         }
-
-                        self.popup_notif.push({
-                            PopupNotif {
-                                message: vec![(
-                                    "Added album to the queue".to_string(),
-                                    Color::White,
-                                )],
-                                border_color: Color::Green,
-                                duration_ticks: Some(NOTIF_DURATION),
-                                title: "".to_string(),
-                                index: 1,
-                            }
+                        self.popup_manual = Some(PopupManual {
+                            title: format!("Scan report ({} skipped)", self.scan_failures.len()),
+                            border_color: if self.scan_failures.is_empty() {
+                                Color::White
+                            } else {
+                                Color::Red
+                            },
+                            bottom_hint: self.scroll_hint(),
+                            message,
+                            scroll: 0,
                         });
                     }
+                    AppEvent::AddSingle => {
+                        let songs = self.selected_song();
+                        self.player.push(songs);
+                    }
+                    AppEvent::AddSingleNext => {
+                        let songs = self.selected_song();
+                        self.player.insert_next(songs);
+                    }
+                    AppEvent::AddAlbum => {
+                        let songs = self.selected_album();
+                        self.player.push(songs);
+                        self.notify("Added album to the queue", Color::Green);
+                    }
+                    AppEvent::AddAlbumNext => {
+                        let songs = self.selected_album();
+                        self.player.insert_next(songs);
+                        self.notify("Playing album next", Color::Green);
+                    }
                     AppEvent::Resume => {
-                        self.sink.play();
+                        self.player.play();
                     }
                     AppEvent::ClearQueue => {
-                        self.sink.clear();
-                        self.queue = Vec::new();
+                        self.player.clear();
                     }
                     AppEvent::Pause => {
-                        self.sink.pause();
+                        self.player.pause();
                     }
                     AppEvent::Skip => {
-                        self.sink.skip_one();
+                        self.player.skip();
+                    }
+                    AppEvent::RemoveFromQueue => {
+                        self.player.remove(self.queue_index);
+                    }
+                    AppEvent::MoveItemUp => {
+                        if self
+                            .player
+                            .swap(self.queue_index, self.queue_index.wrapping_sub(1))
+                        {
+                            self.queue_index -= 1;
+                        }
+                    }
+                    AppEvent::MoveItemDown => {
+                        if self.player.swap(self.queue_index, self.queue_index + 1) {
+                            self.queue_index += 1;
+                        }
+                    }
+                    AppEvent::PlayNow => {
+                        self.player.play_now(self.queue_index);
+                        self.player.play();
+                        self.queue_index = 0;
+                    }
+                    AppEvent::PlayNext => {
+                        if self.queue_index > 1 {
+                            self.player.play_next(self.queue_index);
+                            self.queue_index = 1;
+                        }
+                    }
+                    AppEvent::Previous => {
+                        self.player.previous();
+                    }
+                    AppEvent::CycleRepeat => {
+                        self.player.set_repeat(self.player.repeat().cycle());
+                        self.save_state();
+                    }
+                    AppEvent::Equalizer => {
+                        if self.mode == Mode::Equalizer {
+                            self.save_equalizer();
+                            self.mode = Mode::Sitback;
+                        } else {
+                            self.mode = Mode::Equalizer;
+                        }
+                    }
+                    AppEvent::CycleEqPreset => {
+                        self.player.cycle_eq_preset();
+                    }
+                    AppEvent::CycleReplayGain => {
+                        self.player.set_replaygain(self.player.replaygain().cycle());
+                    }
+                    AppEvent::ToggleCrossfade => {
+                        if self.player.crossfade().is_some() {
+                            self.player.set_crossfade(None);
+                        } else {
+                            self.player.set_crossfade(Some(Duration::from_secs_f64(
+                                self.config.playback.crossfade_seconds,
+                            )));
+                        }
+                    }
+                    AppEvent::Shuffle(shuffle) => {
+                        if self.player.shuffle() == shuffle {
+                            self.player.set_shuffle(Shuffle::Off);
+                        } else {
+                            self.player.set_shuffle(shuffle);
+                        }
+                    }
+                    AppEvent::Playlists => {
+                        if self.mode == Mode::Playlists {
+                            self.mode = Mode::Sitback;
+                        } else {
+                            self.playlists = crate::playlist::playlist_dir(&self.config)
+                                .map(|dir| crate::playlist::list(&dir))
+                                .unwrap_or_default();
+                            self.playlist_index = 0;
+                            self.mode = Mode::Playlists;
+                        }
+                    }
+                    AppEvent::LoopStart => {
+                        if !self.player.queue.is_empty() {
+                            self.player.set_loop_start();
+                        }
+                    }
+                    AppEvent::LoopEnd => {
+                        if let (Some(_), Some(_)) = self.player.ab_loop() {
+                            self.player.clear_loop();
+                        } else if !self.player.set_loop_end() {
+                            self.notify("Set the start of the loop first", Color::Yellow);
+                        }
+                    }
+                    AppEvent::Bookmarks => {
+                        if self.mode == Mode::Bookmarks {
+                            self.mode = Mode::Sitback;
+                        } else {
+                            self.bookmark_index = 0;
+                            self.mode = Mode::Bookmarks;
+                        }
+                    }
+                    AppEvent::NameBookmark => {
+                        if self.player.queue.is_empty() {
+                            self.notify("Nothing is playing", Color::Yellow);
+                        } else {
+                            self.bookmark_name.clear();
+                            self.bookmark_at = self.player.position();
+                            self.mode = Mode::NameBookmark;
+                        }
+                    }
+                    AppEvent::SaveBookmark => {
+                        self.mode = Mode::Sitback;
+                        let name = self.bookmark_name.trim().to_string();
+                        if let Some(song) = self.player.queue.first()
+                            && !name.is_empty()
+                        {
+                            let file_path = song.file_path.clone();
+                            self.bookmarks.add(
+                                &file_path,
+                                Bookmark {
+                                    name,
+                                    position: self.bookmark_at,
+                                },
+                            );
+                            self.save_bookmarks();
+                        }
+                    }
+                    AppEvent::JumpToBookmark => {
+                        if let Some(bookmark) = self.current_bookmarks().get(self.bookmark_index) {
+                            self.player.seek(bookmark.position);
+                        }
+                    }
+                    AppEvent::RemoveBookmark => {
+                        if let Some(song) = self.player.queue.first() {
+                            let file_path = song.file_path.clone();
+                            self.bookmarks.remove(&file_path, self.bookmark_index);
+                            self.bookmark_index = self
+                                .bookmark_index
+                                .min(self.current_bookmarks().len().saturating_sub(1));
+                            self.save_bookmarks();
+                        }
+                    }
+                    AppEvent::CycleSleep => {
+                        let remaining = match self.sleep {
+                            Some(Sleep::At(until)) => {
+                                Some(until.saturating_duration_since(Instant::now()))
+                            }
+                            _ => None,
+                        };
+                        // Pressing again right after setting it moves on instead of starting over
+                        let minutes = match remaining {
+                            None => SLEEP_MINUTES.first(),
+                            Some(remaining) => SLEEP_MINUTES.iter().find(|minutes| {
+                                Duration::from_secs(**minutes * 60)
+                                    > remaining + Duration::from_secs(60)
+                            }),
+                        };
+                        self.set_sleep(minutes.map(|minutes| {
+                            Sleep::At(Instant::now() + Duration::from_secs(minutes * 60))
+                        }));
+                    }
+                    AppEvent::StopAfterCurrent => {
+                        if self.player.stop_after().is_some() {
+                            self.set_sleep(self.sleep.filter(|sleep| *sleep != Sleep::Songs));
+                            self.player.set_stop_after(None);
+                        } else {
+                            self.player.set_stop_after(Some(1));
+                        }
+                    }
+                    AppEvent::NamePlaylist => {
+                        if self.player.queue.is_empty() {
+                            self.notify("The queue is empty, nothing to save", Color::Yellow);
+                        } else {
+                            self.playlist_name.clear();
+                            self.mode = Mode::SavePlaylist;
+                        }
+                    }
+                    AppEvent::SavePlaylist => {
+                        self.mode = Mode::Sitback;
+                        let name = self.playlist_name.trim().replace('/', "-");
+                        // Playlists are always written as M3U, any other name gets the extension
+                        let is_m3u = Path::new(&name).extension().is_some_and(|e| {
+                            e.eq_ignore_ascii_case("m3u") || e.eq_ignore_ascii_case("m3u8")
+                        });
+                        let file_name = if is_m3u { name } else { format!("{name}.m3u8") };
+                        if let Some(dir) = crate::playlist::playlist_dir(&self.config)
+                            && !self.playlist_name.trim().is_empty()
+                        {
+                            let path = dir.join(file_name);
+                            match crate::playlist::save(&path, &self.player.queue) {
+                                Ok(()) => self.notify(
+                                    &format!(
+                                        "Saved {} songs to {}",
+                                        self.player.queue.len(),
+                                        path.display()
+                                    ),
+                                    Color::Green,
+                                ),
+                                Err(err) => self.notify(
+                                    &format!("Couldn't write {}: {err}", path.display()),
+                                    Color::Red,
+                                ),
+                            }
+                        }
+                    }
+                    AppEvent::LoadPlaylist => {
+                        if let Some(path) = self.playlists.get(self.playlist_index).cloned() {
+                            self.load_playlist(&path);
+                        }
+                    }
+                    AppEvent::History => {
+                        self.mode = if self.mode == Mode::History {
+                            Mode::Sitback
+                        } else {
+                            Mode::History
+                        };
                     }
                     AppEvent::VolumeUp => {
-                        let get_volume = self.sink.volume();
-                        if get_volume + VOLUME_CHANGE > 1.0 {
-                            self.sink.set_volume(1.0);
+                        let get_volume = self.base_volume();
+                        let step = self.config.playback.volume_step;
+                        if get_volume + step > 1.0 {
+                            self.set_base_volume(1.0);
                         } else {
-                            self.sink.set_volume(get_volume + VOLUME_CHANGE);
+                            self.set_base_volume(get_volume + step);
                         };
                     }
                     AppEvent::VolumeDown => {
-                        let get_volume = self.sink.volume();
-                        if get_volume - VOLUME_CHANGE < 0.0 {
-                            self.sink.set_volume(0.0);
+                        let get_volume = self.base_volume();
+                        let step = self.config.playback.volume_step;
+                        if get_volume - step < 0.0 {
+                            self.set_base_volume(0.0);
                         } else {
-                            self.sink.set_volume(get_volume - VOLUME_CHANGE);
+                            self.set_base_volume(get_volume - step);
                         };
                     }
 
+                    AppEvent::SpeedUp => {
+                        let speed = self.player.speed() + self.config.playback.speed_step;
+                        self.change_speed(speed);
+                    }
+                    AppEvent::SpeedDown => {
+                        let speed = self.player.speed() - self.config.playback.speed_step;
+                        self.change_speed(speed);
+                    }
+                    AppEvent::ResetSpeed => {
+                        self.change_speed(1.0);
+                    }
                     AppEvent::MoveForward => {
-                        let pos = self.sink.get_pos();
-                        if !self.queue.is_empty() && pos + SEEK_CHANGE < self.queue[0].duration {
-                            let _ = self.sink.try_seek(pos + SEEK_CHANGE);
+                        let pos = self.player.position();
+                        let seek_change = Duration::from_secs(self.config.playback.seek_seconds);
+                        if let Some(song) = self.player.queue.first()
+                            && pos + seek_change < song.duration
+                        {
+                            self.player.seek(pos + seek_change);
                         }
                     }
-                    AppEvent::MoveBackward => {
-                        let pos = self.sink.get_pos();
-
-                        if !self.queue.is_empty() {
-                            if pos.as_secs() as i32 - SEEK_CHANGE.as_secs() as i32 > 0 {
-                                let _ = self.sink.try_seek(pos - SEEK_CHANGE);
-                            } else {
-                                let _ = self.sink.try_seek(Duration::from_millis(0));
-                            }
+                    AppEvent::SeekTo(position) => {
+                        if let Some(song) = self.player.queue.first() {
+                            self.player.seek(position.min(song.duration));
                         }
                     }
+                    AppEvent::RunCommand => {
+                        self.mode = Mode::Sitback;
+                        self.run_command();
+                    }
+                    AppEvent::MoveBackward => {
+                        let pos = self.player.position();
+                        let seek_change = Duration::from_secs(self.config.playback.seek_seconds);
+                        self.player.seek(pos.saturating_sub(seek_change));
+                    }
                     AppEvent::RefreshResults => {
                         if !self.query.is_empty() {
                             let matcher = SkimMatcherV2::default();
                             let mut entries_with_score: Vec<(Song, i64)> = Vec::new();
                             for entry in self.search_cache.clone() {
-                                if let Some(score) = matcher.fuzzy_match(
-                                    {
-                                        match self.search_by {
-                                            SearchBy::FilePath => entry.file_path.as_str(),
-                                            SearchBy::Title => entry.title.as_str(),
-                                            SearchBy::Artist => entry.artist.as_str(),
-                                            SearchBy::Album => entry.album.as_str(),
-                                            SearchBy::Genre => entry.genre.as_str(),
-                                        }
-                                    },
-                                    self.query.as_str(),
-                                ) {
-                                    if score > 0 {
-                                        entries_with_score.push((entry, score));
-                                    };
+                                if let Some(score) = self.match_score(&matcher, &entry) {
+                                    entries_with_score.push((entry, score));
                                 }
                             }
 
-                            entries_with_score.sort_by(|a, b| b.1.cmp(&a.1));
+                            entries_with_score.sort_by_key(|e| std::cmp::Reverse(e.1));
                             self.search_results = Vec::new();
                             for entry in entries_with_score {
                                 self.search_results.push(entry.0)
@@ -470,20 +1245,50 @@ impl App {
                             self.select_index = (self.search_results.len() as i32 - 1_i32) as usize;
                         }
                     }
-                    AppEvent::RefreshCache => {
-                        let src = self.sources.clone();
-                        self.search_cache = App::search_directories(src);
+                    AppEvent::RefreshCache => self.start_scan(),
+                    AppEvent::ScanBatch(batch) => {
+                        if let Some(scan) = &mut self.scan {
+                            scan.progress = batch.progress;
+                            if scan.live {
+                                self.search_cache
+                                    .extend(batch.entries.iter().map(|e| e.song.clone()));
+                                if self.mode == Mode::Search {
+                                    self.events.send(AppEvent::RefreshResults);
+                                }
+                            }
+                            scan.entries.extend(batch.entries);
+                            scan.failures.extend(batch.failures);
+                        }
                     }
-                    AppEvent::InitPopup => {
-                        self.popup_manual = Some(PopupManual {
-                            title: "".to_string(),
-                            border_color: Color::Blue,
-                            bottom_hint: PopupManual::default().bottom_hint,
-                            message: vec![(
-                                "Scanning your directories...".to_string(),
-                                Color::White,
-                            )],
-                        });
+                    AppEvent::FsChanged(paths) => {
+                        if let Some(watcher) = &mut self.watcher {
+                            watcher.push(paths);
+                        }
+                    }
+                    AppEvent::LibraryUpdate(update) => self.apply_library_update(update),
+                    AppEvent::PlaylistLoaded(name, songs) => self.add_playlist(name, songs),
+                    AppEvent::LibrarySaveFailed(err) => self.notify(
+                        &format!("Couldn't save the library cache: {err}"),
+                        Color::Red,
+                    ),
+                    AppEvent::ScanFinished => {
+                        if let Some(scan) = self.scan.take() {
+                            self.library = LibraryCache::new(scan.entries);
+                            self.search_cache = self.library.songs();
+                            self.save_library();
+                            if self.mode == Mode::Search {
+                                self.events.send(AppEvent::RefreshResults);
+                            }
+                            self.scan_failures = scan.failures;
+                            if !self.scan_failures.is_empty() {
+                                let message = format!(
+                                    "Skipped {} unreadable files, press {} for the report",
+                                    self.scan_failures.len(),
+                                    self.config.keys.scan_report
+                                );
+                                self.notify(&message, Color::Red);
+                            }
+                        }
                     }
                 },
             }
@@ -496,6 +1301,17 @@ impl App {
         if self.popup_manual.is_some() && key_event.kind == KeyEventKind::Press {
             match key_event.code {
                 KeyCode::Esc => self.events.send(AppEvent::Escape),
+                KeyCode::Char(c) if c == self.config.keys.move_down => {
+                    let screen = Rect::new(0, 0, self.terminal_size.0, self.terminal_size.1);
+                    if let Some(popup) = &mut self.popup_manual {
+                        popup.scroll = popup.scroll.saturating_add(1).min(popup.max_scroll(screen));
+                    }
+                }
+                KeyCode::Char(c) if c == self.config.keys.move_up => {
+                    if let Some(popup) = &mut self.popup_manual {
+                        popup.scroll = popup.scroll.saturating_sub(1);
+                    }
+                }
                 _ => {}
             }
         } else if self.popup_manual.is_none() && key_event.kind == KeyEventKind::Press {
@@ -507,95 +1323,255 @@ impl App {
                         self.events.send(AppEvent::Resume)
                     } else if self.mode == Mode::Search {
                         self.events.send(AppEvent::Select)
+                    } else if self.mode == Mode::Sitback {
+                        self.events.send(AppEvent::PlayNow)
+                    } else if self.mode == Mode::Playlists {
+                        self.events.send(AppEvent::LoadPlaylist)
+                    } else if self.mode == Mode::SavePlaylist {
+                        self.events.send(AppEvent::SavePlaylist)
+                    } else if self.mode == Mode::Equalizer {
+                        self.events.send(AppEvent::CycleEqPreset)
+                    } else if self.mode == Mode::Bookmarks {
+                        self.events.send(AppEvent::JumpToBookmark)
+                    } else if self.mode == Mode::NameBookmark {
+                        self.events.send(AppEvent::SaveBookmark)
+                    } else if self.mode == Mode::Command {
+                        self.events.send(AppEvent::RunCommand)
                     }
                 }
                 _ => {}
             }
-            if self.mode != Mode::Search {
+            if self.mode == Mode::SavePlaylist {
                 match key_event.code {
-                    // Select only
-                    KeyCode::Char('j') if self.mode == Mode::Select => {
+                    KeyCode::Char(c) => self.playlist_name.push(c),
+                    KeyCode::Backspace => {
+                        self.playlist_name.pop();
+                    }
+                    _ => {}
+                }
+            } else if self.mode == Mode::NameBookmark {
+                match key_event.code {
+                    KeyCode::Char(c) => self.bookmark_name.push(c),
+                    KeyCode::Backspace => {
+                        self.bookmark_name.pop();
+                    }
+                    _ => {}
+                }
+            } else if self.mode == Mode::Command {
+                match key_event.code {
+                    KeyCode::Char(c) => self.command.push(c),
+                    // Like vim, deleting past the start leaves the command line
+                    KeyCode::Backspace if self.command.pop().is_none() => {
+                        self.mode = Mode::Sitback;
+                    }
+                    _ => {}
+                }
+            } else if self.mode != Mode::Search {
+                let keys = self.config.keys;
+                match key_event.code {
+                    KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
+                        self.events.send(AppEvent::Quit)
+                    }
+                    // Lists with a cursor
+                    KeyCode::Char(c)
+                        if c == keys.move_down
+                            && matches!(
+                                self.mode,
+                                Mode::Select
+                                    | Mode::Sitback
+                                    | Mode::Playlists
+                                    | Mode::Equalizer
+                                    | Mode::Bookmarks
+                            ) =>
+                    {
                         self.events.send(AppEvent::MoveDown)
                     }
-                    KeyCode::Char('k') if self.mode == Mode::Select => {
+                    KeyCode::Char(c)
+                        if c == keys.move_up
+                            && matches!(
+                                self.mode,
+                                Mode::Select
+                                    | Mode::Sitback
+                                    | Mode::Playlists
+                                    | Mode::Equalizer
+                                    | Mode::Bookmarks
+                            ) =>
+                    {
                         self.events.send(AppEvent::MoveUp)
                     }
-                    KeyCode::Char('q') => self.events.send(AppEvent::Quit),
-                    KeyCode::Char('p') => {
-                        if self.mode == Mode::Sitback {
-                            if self.sink.is_paused() {
-                                self.events.send(AppEvent::Resume);
-                            } else {
-                                self.events.send(AppEvent::Pause);
-                            }
+                    // Sitback only
+                    KeyCode::Char(c)
+                        if c == keys.remove_from_queue && self.mode == Mode::Sitback =>
+                    {
+                        self.events.send(AppEvent::RemoveFromQueue)
+                    }
+                    KeyCode::Char(c) if c == keys.move_item_up && self.mode == Mode::Sitback => {
+                        self.events.send(AppEvent::MoveItemUp)
+                    }
+                    KeyCode::Char(c) if c == keys.move_item_down && self.mode == Mode::Sitback => {
+                        self.events.send(AppEvent::MoveItemDown)
+                    }
+                    KeyCode::Char(c) if c == keys.play_next && self.mode == Mode::Sitback => {
+                        self.events.send(AppEvent::PlayNext)
+                    }
+                    // Bookmarks only
+                    KeyCode::Char(c)
+                        if c == keys.remove_from_queue && self.mode == Mode::Bookmarks =>
+                    {
+                        self.events.send(AppEvent::RemoveBookmark)
+                    }
+                    // Select only
+                    KeyCode::Char(c) if c == keys.play_next && self.mode == Mode::Select => {
+                        self.events.send(AppEvent::AddSingleNext);
+                        self.events.send(AppEvent::Resume)
+                    }
+                    KeyCode::Char(c) if c == keys.album_next && self.mode == Mode::Select => {
+                        self.events.send(AppEvent::AddAlbumNext);
+                        self.events.send(AppEvent::Resume)
+                    }
+                    KeyCode::Char(c) if c == keys.quit => self.events.send(AppEvent::Quit),
+                    KeyCode::Char(c)
+                        if c == keys.pause
+                            && (self.mode == Mode::Sitback || self.mode == Mode::History) =>
+                    {
+                        if self.player.is_paused() {
+                            self.events.send(AppEvent::Resume);
+                        } else {
+                            self.events.send(AppEvent::Pause);
                         }
                     }
 
-                    KeyCode::Char('s') => {
+                    KeyCode::Char(c) if c == keys.skip => {
                         self.events.send(AppEvent::Skip);
                     }
+                    KeyCode::Char(c) if c == keys.previous => {
+                        self.events.send(AppEvent::Previous);
+                    }
+                    KeyCode::Char(c) if c == keys.history => {
+                        self.events.send(AppEvent::History);
+                    }
+                    KeyCode::Char(c) if c == keys.playlists => {
+                        self.events.send(AppEvent::Playlists);
+                    }
+                    KeyCode::Char(c) if c == keys.save_playlist => {
+                        self.events.send(AppEvent::NamePlaylist);
+                    }
+                    KeyCode::Char(c) if c == keys.repeat => {
+                        self.events.send(AppEvent::CycleRepeat);
+                    }
+                    KeyCode::Char(c) if c == keys.loop_start => {
+                        self.events.send(AppEvent::LoopStart);
+                    }
+                    KeyCode::Char(c) if c == keys.loop_end => {
+                        self.events.send(AppEvent::LoopEnd);
+                    }
+                    KeyCode::Char(c) if c == keys.sleep => {
+                        self.events.send(AppEvent::CycleSleep);
+                    }
+                    KeyCode::Char(c) if c == keys.stop_after_current => {
+                        self.events.send(AppEvent::StopAfterCurrent);
+                    }
+                    KeyCode::Char(c) if c == keys.bookmark => {
+                        self.events.send(AppEvent::NameBookmark);
+                    }
+                    KeyCode::Char(c) if c == keys.bookmarks => {
+                        self.events.send(AppEvent::Bookmarks);
+                    }
+                    KeyCode::Char(c) if c == keys.equalizer => {
+                        self.events.send(AppEvent::Equalizer);
+                    }
+                    KeyCode::Char(c) if c == keys.replaygain => {
+                        self.events.send(AppEvent::CycleReplayGain);
+                    }
+                    KeyCode::Char(c) if c == keys.crossfade => {
+                        self.events.send(AppEvent::ToggleCrossfade);
+                    }
+                    KeyCode::Char(c) if c == keys.shuffle => {
+                        self.events.send(AppEvent::Shuffle(Shuffle::Tracks));
+                    }
+                    KeyCode::Char(c) if c == keys.album_shuffle => {
+                        self.events.send(AppEvent::Shuffle(Shuffle::Albums));
+                    }
 
-                    KeyCode::Char('a') => {
-                        if self.mode == Mode::Select {
-                            self.events.send(AppEvent::AddAlbum);
-                            self.events.send(AppEvent::Resume)
-                        }
+                    KeyCode::Char(c) if c == keys.add_album && self.mode == Mode::Select => {
+                        self.events.send(AppEvent::AddAlbum);
+                        self.events.send(AppEvent::Resume)
                     }
-                    KeyCode::Char('v') => {
+                    KeyCode::Char(c) if c == keys.volume_down => {
                         self.events.send(AppEvent::VolumeDown);
                     }
-                    KeyCode::Char('h') => {
+                    KeyCode::Char(c) if c == keys.seek_backward => {
                         self.events.send(AppEvent::MoveBackward);
                     }
-                    KeyCode::Char('l') => {
+                    KeyCode::Char(c) if c == keys.seek_forward => {
                         self.events.send(AppEvent::MoveForward);
                     }
-                    KeyCode::Char('V') => {
+                    KeyCode::Char(c) if c == keys.speed_up => {
+                        self.events.send(AppEvent::SpeedUp);
+                    }
+                    KeyCode::Char(c) if c == keys.speed_down => {
+                        self.events.send(AppEvent::SpeedDown);
+                    }
+                    KeyCode::Char(c) if c == keys.reset_speed => {
+                        self.events.send(AppEvent::ResetSpeed);
+                    }
+                    KeyCode::Char(c) if c == keys.volume_up => {
                         self.events.send(AppEvent::VolumeUp);
                     }
-                    KeyCode::Char(':') => {
+                    KeyCode::Char(c) if c == keys.help => {
                         self.events.send(AppEvent::HelpDesk);
                     }
-                    KeyCode::Char('1') => {
+                    KeyCode::Char(c) if c == keys.command => {
+                        self.command.clear();
+                        self.mode = Mode::Command;
+                    }
+                    KeyCode::Char(c) if c == keys.scan_report => {
+                        self.events.send(AppEvent::ScanReport);
+                    }
+                    KeyCode::Char('1') if self.mode == Mode::Select => {
                         self.search_by = SearchBy::FilePath;
                         self.events.send(AppEvent::RefreshResults);
                     }
-                    KeyCode::Char('2') => {
+                    KeyCode::Char('2') if self.mode == Mode::Select => {
                         self.search_by = SearchBy::Title;
                         self.events.send(AppEvent::RefreshResults);
                     }
-                    KeyCode::Char('3') => {
+                    KeyCode::Char('3') if self.mode == Mode::Select => {
                         self.search_by = SearchBy::Artist;
                         self.events.send(AppEvent::RefreshResults);
                     }
-                    KeyCode::Char('4') => {
+                    KeyCode::Char('4') if self.mode == Mode::Select => {
                         self.search_by = SearchBy::Album;
                         self.events.send(AppEvent::RefreshResults);
                     }
-                    KeyCode::Char('5') => {
+                    KeyCode::Char('5') if self.mode == Mode::Select => {
                         self.search_by = SearchBy::Genre;
                         self.events.send(AppEvent::RefreshResults);
                     }
 
-                    KeyCode::Char('c' | 'C') => {
-                        if key_event.modifiers == KeyModifiers::CONTROL {
-                            self.events.send(AppEvent::Quit)
-                        } else {
-                            self.events.send(AppEvent::ClearQueue);
-
-                            self.popup_notif.push({
-                                PopupNotif {
-                                    message: vec![("Cleared the queue".to_string(), Color::White)],
-                                    border_color: Color::Yellow,
-                                    duration_ticks: Some(NOTIF_DURATION),
-                                    title: "".to_string(),
-                                    index: self.popup_notif.len() + 1,
-                                }
-                            });
+                    // Outside of select mode digits jump to 0-90% of the song
+                    KeyCode::Char(c @ '0'..='9') if self.mode != Mode::Select => {
+                        if let Some(song) = self.player.queue.first() {
+                            let tenths = c.to_digit(10).unwrap_or(0);
+                            self.events
+                                .send(AppEvent::SeekTo(song.duration * tenths / 10));
                         }
                     }
+                    KeyCode::Char(c) if c == keys.clear_queue || c == CLEAR_QUEUE_ALT => {
+                        self.events.send(AppEvent::ClearQueue);
 
-                    KeyCode::Char('/') => self.events.send(AppEvent::Search),
+                        self.popup_notif.push({
+                            PopupNotif {
+                                message: vec![("Cleared the queue".to_string(), Color::White)],
+                                border_color: Color::Yellow,
+                                duration_ticks: Some(self.config.ui.notif_duration),
+                                title: "".to_string(),
+                                index: self.popup_notif.len() + 1,
+                            }
+                        });
+                    }
+
+                    KeyCode::Char(c) if c == keys.search => self.events.send(AppEvent::Search),
 
                     // Other handlers you could add here.
                     _ => {}
@@ -636,10 +1612,33 @@ impl App {
     /// The tick event is where you can update the state of your application with any logic that
     /// needs to be updated at a fixed frame rate. E.g. polling a server, updating an animation.
     pub fn tick(&mut self) {
-        if self.sink.len() < self.queue.len() {
-            self.queue.remove(0);
+        // Songs already known to be invalid are dropped quietly
+        for song in self.player.update().into_iter().filter(|s| s.is_valid) {
+            self.mark_invalid(&song);
+        }
+        self.update_sleep();
+        self.queue_index = self
+            .queue_index
+            .min(self.player.queue.len().saturating_sub(1));
+        if self.state_saved.elapsed() >= STATE_SAVE_INTERVAL {
+            self.save_state();
+        }
+        if self
+            .library_changed
+            .is_some_and(|changed| changed.elapsed() >= LIBRARY_SAVE_DELAY)
+        {
+            self.save_library();
+        }
+        // Changes are held back while scanning, the scan would overwrite them when it finishes
+        if self.scan.is_none()
+            && let Some(paths) = self.watcher.as_mut().and_then(|w| w.take_settled())
+        {
+            let sources = self.source_rules.clone();
+            let sender = self.events.sender();
+            tokio::task::spawn_blocking(move || {
+                crate::library::rescan_paths(paths, sources, sender)
+            });
         }
-        if !self.popup_notif.is_empty() {}
         let mut n_idx = Vec::new();
         for (i, notif) in self.popup_notif.iter_mut().enumerate() {
             if let Some(t) = notif.duration_ticks {
@@ -660,8 +1659,141 @@ impl App {
         }
     }
 
+    /// Handles the mouse, a click on the progress bar seeks there.
+    pub fn handle_mouse_events(&mut self, mouse_event: MouseEvent) {
+        if mouse_event.kind != MouseEventKind::Down(MouseButton::Left)
+            || self.popup_manual.is_some()
+            || !self.shows_gauge()
+        {
+            return;
+        }
+        let screen = Rect::new(0, 0, self.terminal_size.0, self.terminal_size.1);
+        let gauge = crate::ui::gauge_area(self, screen);
+        if let Some(song) = self.player.queue.first()
+            && gauge.width > 0
+            && (gauge.left()..gauge.right()).contains(&mouse_event.column)
+            && (gauge.top()..gauge.bottom()).contains(&mouse_event.row)
+        {
+            let ratio = (mouse_event.column - gauge.x) as f64 / gauge.width as f64;
+            self.events
+                .send(AppEvent::SeekTo(song.duration.mul_f64(ratio)));
+        }
+    }
+
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
+        // Saved right away, a background save might not finish before the app exits
+        if self.library_changed.take().is_some() {
+            let _ = self.library.save();
+        }
+        self.save_state();
+        self.save_equalizer();
         self.running = false;
     }
 }
+
+/// Position of every song by its path.
+fn index_by_path<'a>(songs: impl Iterator<Item = &'a Song>) -> HashMap<String, usize> {
+    songs
+        .enumerate()
+        .map(|(i, song)| (song.file_path.clone(), i))
+        .collect()
+}
+
+/// Reads a time like `90`, `2:31` or `1:02:03`. Only the first field may go past 59, `2:75` is
+/// more likely a typo than 3:15.
+fn parse_time(text: &str) -> Option<Duration> {
+    let mut seconds = 0.0;
+    for (i, part) in text.split(':').enumerate() {
+        let value: f64 = part.parse().ok()?;
+        if !(value >= 0.0 && value.is_finite()) || i > 2 || (i > 0 && value >= 60.0) {
+            return None;
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// Where a `:seek` argument points to: a time, a time relative to `position` with `+` or `-`, or
+/// a percentage of `duration`. Positions past either end are clamped to it.
+fn parse_seek(argument: &str, position: Duration, duration: Duration) -> Option<Duration> {
+    let target = if let Some(percent) = argument.strip_suffix('%') {
+        let percent = percent
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|p| p.is_finite())?;
+        duration.mul_f64((percent / 100.0).clamp(0.0, 1.0))
+    } else if let Some(forward) = argument.strip_prefix('+') {
+        position.saturating_add(parse_time(forward)?)
+    } else if let Some(backward) = argument.strip_prefix('-') {
+        position.saturating_sub(parse_time(backward)?)
+    } else {
+        parse_time(argument)?
+    };
+    Some(target.min(duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONG: Duration = Duration::from_secs(240);
+
+    fn secs(seconds: u64) -> Option<Duration> {
+        Some(Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn time_reads_seconds_minutes_and_hours() {
+        assert_eq!(parse_time("90"), secs(90));
+        assert_eq!(parse_time("2:31"), secs(151));
+        assert_eq!(parse_time("1:02:03"), secs(3723));
+        assert_eq!(parse_time("0:07.5"), Some(Duration::from_millis(7500)));
+    }
+
+    #[test]
+    fn time_rejects_malformed_input() {
+        assert_eq!(parse_time(""), None);
+        assert_eq!(parse_time("2:"), None);
+        assert_eq!(parse_time("2:75"), None);
+        assert_eq!(parse_time("1:00:00:00"), None);
+        assert_eq!(parse_time("-5"), None);
+        assert_eq!(parse_time("inf"), None);
+        assert_eq!(parse_time("NaN"), None);
+        assert_eq!(parse_time("1e300"), None);
+    }
+
+    #[test]
+    fn seek_to_a_time_stays_within_the_song() {
+        assert_eq!(parse_seek("2:31", Duration::from_secs(10), SONG), secs(151));
+        assert_eq!(parse_seek("5:00", Duration::from_secs(10), SONG), secs(240));
+        assert_eq!(parse_seek("", Duration::from_secs(10), SONG), None);
+        assert_eq!(parse_seek("2:75", Duration::from_secs(10), SONG), None);
+    }
+
+    #[test]
+    fn seek_relative_to_the_position() {
+        assert_eq!(parse_seek("+30", Duration::from_secs(100), SONG), secs(130));
+        assert_eq!(
+            parse_seek("-1:00", Duration::from_secs(100), SONG),
+            secs(40)
+        );
+        // Clamped to the start and the end of the song
+        assert_eq!(parse_seek("-1:00", Duration::from_secs(20), SONG), secs(0));
+        assert_eq!(
+            parse_seek("+1:00", Duration::from_secs(220), SONG),
+            secs(240)
+        );
+        assert_eq!(parse_seek("+", Duration::from_secs(20), SONG), None);
+    }
+
+    #[test]
+    fn seek_to_a_percentage() {
+        assert_eq!(parse_seek("50%", Duration::ZERO, SONG), secs(120));
+        assert_eq!(parse_seek("150%", Duration::ZERO, SONG), secs(240));
+        assert_eq!(parse_seek("-20%", Duration::ZERO, SONG), secs(0));
+        assert_eq!(parse_seek("%", Duration::ZERO, SONG), None);
+        assert_eq!(parse_seek("NaN%", Duration::ZERO, SONG), None);
+    }
+}
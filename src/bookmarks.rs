@@ -0,0 +1,56 @@
+use crate::persist::{read_json, write_json_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A named position in a song.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub position: Duration,
+}
+
+/// Bookmarks of every song that has any, by file path.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bookmarks {
+    pub files: HashMap<String, Vec<Bookmark>>,
+}
+
+impl Bookmarks {
+    pub fn bookmarks_file() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rrplay").join("bookmarks.json"))
+    }
+
+    /// Reads the bookmarks from disk, starting without any if they are missing or unreadable.
+    pub fn load() -> Self {
+        read_json(Bookmarks::bookmarks_file()).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        write_json_atomic(Bookmarks::bookmarks_file(), self)
+    }
+
+    /// The bookmarks of a song, in the order they appear in it.
+    pub fn get(&self, file_path: &str) -> &[Bookmark] {
+        self.files.get(file_path).map_or(&[], |b| b.as_slice())
+    }
+
+    pub fn add(&mut self, file_path: &str, bookmark: Bookmark) {
+        let bookmarks = self.files.entry(file_path.to_string()).or_default();
+        let index = bookmarks.partition_point(|b| b.position <= bookmark.position);
+        bookmarks.insert(index, bookmark);
+    }
+
+    pub fn remove(&mut self, file_path: &str, index: usize) {
+        if let Some(bookmarks) = self.files.get_mut(file_path) {
+            if index < bookmarks.len() {
+                bookmarks.remove(index);
+            }
+            if bookmarks.is_empty() {
+                self.files.remove(file_path);
+            }
+        }
+    }
+}
@@ -0,0 +1,505 @@
+use crate::eq::{EqPresets, Gains, MAX_GAIN};
+use crate::player::ReplayGain;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Clears the queue on top of `keys.clear_queue`, so no other key can be bound to it.
+pub const CLEAR_QUEUE_ALT: char = 'C';
+
+/// User configuration, read from `~/.config/rrplay/config.toml`.
+///
+/// Every section and field is optional, anything missing falls back to its default.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub sources: Vec<SourceConfig>,
+    pub library: LibraryConfig,
+    pub playback: PlaybackConfig,
+    pub eq: EqConfig,
+    pub ui: UiConfig,
+    pub keys: Keys,
+}
+
+/// A directory to look for music in.
+///
+/// Glob patterns are matched against paths relative to `path`, e.g. `**/Stems/**`. A file is
+/// picked up if it has one of the extensions, matches any `include` pattern (or there are none)
+/// and neither it nor any directory above it matches an `exclude` pattern.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceConfig {
+    pub path: String,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// How many directories deep to look, 1 only takes the files directly inside `path`.
+    pub max_depth: Option<usize>,
+    /// Whether to descend into symlinked directories.
+    pub follow_symlinks: bool,
+    /// Extensions to pick up, without the dot. Leaving it empty uses the built in list.
+    pub extensions: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LibraryConfig {
+    /// Threads probing files during a scan, 0 uses one per core.
+    pub scan_threads: usize,
+    /// Where playlists are saved and looked for, `~/.local/share/rrplay/playlists` if unset.
+    pub playlist_dir: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaybackConfig {
+    /// How much the volume changes per key press, between 0 and 1.
+    pub volume_step: f32,
+    /// How far seeking moves, in seconds.
+    pub seek_seconds: u64,
+    /// Whether to bring back the queue, position and volume of the last run, paused.
+    pub restore_session: bool,
+    /// Whether songs fade into each other at startup, can be toggled while running.
+    pub crossfade: bool,
+    /// How long songs overlap when crossfading.
+    pub crossfade_seconds: f64,
+    /// Plays songs of the same album back to back even with crossfade on.
+    pub album_mode: bool,
+    /// ReplayGain mode at startup: "off", "track" or "album".
+    pub replaygain: ReplayGain,
+    /// How much the playback speed changes per key press.
+    pub speed_step: f32,
+    /// Whether songs played faster or slower keep their pitch.
+    pub keep_pitch: bool,
+    /// How long the sleep timer fades out before stopping, 0 stops right away.
+    pub sleep_fade_seconds: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqConfig {
+    /// Preset to start with, "auto" picks one by the genre of each song.
+    pub preset: String,
+    /// Presets on top of the built in ones, the gains of the bands from 31 Hz to 16 kHz in dB.
+    pub presets: BTreeMap<String, Gains>,
+    /// Presets for genres when picking by genre. Genres named like a preset don't need one.
+    pub genres: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// How many times per second the interface refreshes.
+    pub tick_fps: f64,
+    /// How long notifications stay up, in ticks.
+    pub notif_duration: usize,
+    pub colors: Colors,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Colors {
+    /// Focused window and selected search field.
+    pub accent: Color,
+    /// Songs that can be played.
+    pub valid: Color,
+    /// Songs that are missing or couldn't be decoded.
+    pub invalid: Color,
+    pub playing: Color,
+    pub paused: Color,
+}
+
+/// Keybindings outside of search mode.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keys {
+    pub quit: char,
+    pub search: char,
+    pub help: char,
+    pub command: char,
+    pub scan_report: char,
+    pub move_down: char,
+    pub move_up: char,
+    pub pause: char,
+    pub skip: char,
+    pub previous: char,
+    pub history: char,
+    pub playlists: char,
+    pub save_playlist: char,
+    pub repeat: char,
+    pub crossfade: char,
+    pub replaygain: char,
+    pub equalizer: char,
+    pub shuffle: char,
+    pub album_shuffle: char,
+    pub add_album: char,
+    pub album_next: char,
+    pub volume_up: char,
+    pub volume_down: char,
+    pub speed_up: char,
+    pub speed_down: char,
+    pub reset_speed: char,
+    pub seek_forward: char,
+    pub seek_backward: char,
+    pub clear_queue: char,
+    pub remove_from_queue: char,
+    pub move_item_up: char,
+    pub move_item_down: char,
+    pub play_next: char,
+    pub loop_start: char,
+    pub loop_end: char,
+    pub bookmark: char,
+    pub bookmarks: char,
+    pub sleep: char,
+    pub stop_after_current: char,
+}
+
+impl Default for PlaybackConfig {
+    fn default() -> Self {
+        PlaybackConfig {
+            volume_step: 0.05,
+            seek_seconds: 5,
+            restore_session: true,
+            crossfade: false,
+            crossfade_seconds: 5.0,
+            album_mode: true,
+            replaygain: ReplayGain::default(),
+            speed_step: 0.25,
+            keep_pitch: true,
+            sleep_fade_seconds: 30.0,
+        }
+    }
+}
+
+impl Default for EqConfig {
+    fn default() -> Self {
+        EqConfig {
+            preset: crate::eq::AUTO.to_string(),
+            presets: BTreeMap::new(),
+            genres: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            tick_fps: 30.0,
+            notif_duration: 60,
+            colors: Colors::default(),
+        }
+    }
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            accent: Color::Yellow,
+            valid: Color::Green,
+            invalid: Color::Red,
+            playing: Color::Green,
+            paused: Color::Red,
+        }
+    }
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Keys {
+            quit: 'q',
+            search: '/',
+            help: '?',
+            command: ':',
+            scan_report: 'r',
+            move_down: 'j',
+            move_up: 'k',
+            pause: 'p',
+            skip: 's',
+            previous: 'b',
+            history: 'H',
+            playlists: 'P',
+            save_playlist: 'w',
+            repeat: 'R',
+            crossfade: 'f',
+            replaygain: 'g',
+            equalizer: 'E',
+            shuffle: 'x',
+            album_shuffle: 'X',
+            add_album: 'a',
+            album_next: 'N',
+            volume_up: 'V',
+            volume_down: 'v',
+            speed_up: '+',
+            speed_down: '-',
+            reset_speed: '=',
+            seek_forward: 'l',
+            seek_backward: 'h',
+            clear_queue: 'c',
+            remove_from_queue: 'd',
+            move_item_up: 'K',
+            move_item_down: 'J',
+            play_next: 'n',
+            loop_start: 'A',
+            loop_end: 'B',
+            bookmark: 'm',
+            bookmarks: '\'',
+            sleep: 'z',
+            stop_after_current: 'S',
+        }
+    }
+}
+
+impl Keys {
+    /// Every binding along with its name in the config file.
+    pub fn bindings(&self) -> Vec<(&'static str, char)> {
+        vec![
+            ("quit", self.quit),
+            ("search", self.search),
+            ("help", self.help),
+            ("command", self.command),
+            ("scan_report", self.scan_report),
+            ("move_down", self.move_down),
+            ("move_up", self.move_up),
+            ("pause", self.pause),
+            ("skip", self.skip),
+            ("previous", self.previous),
+            ("history", self.history),
+            ("playlists", self.playlists),
+            ("save_playlist", self.save_playlist),
+            ("repeat", self.repeat),
+            ("crossfade", self.crossfade),
+            ("replaygain", self.replaygain),
+            ("equalizer", self.equalizer),
+            ("shuffle", self.shuffle),
+            ("album_shuffle", self.album_shuffle),
+            ("add_album", self.add_album),
+            ("album_next", self.album_next),
+            ("volume_up", self.volume_up),
+            ("volume_down", self.volume_down),
+            ("speed_up", self.speed_up),
+            ("speed_down", self.speed_down),
+            ("reset_speed", self.reset_speed),
+            ("seek_forward", self.seek_forward),
+            ("seek_backward", self.seek_backward),
+            ("clear_queue", self.clear_queue),
+            ("remove_from_queue", self.remove_from_queue),
+            ("move_item_up", self.move_item_up),
+            ("move_item_down", self.move_item_down),
+            ("play_next", self.play_next),
+            ("loop_start", self.loop_start),
+            ("loop_end", self.loop_end),
+            ("bookmark", self.bookmark),
+            ("bookmarks", self.bookmarks),
+            ("sleep", self.sleep),
+            ("stop_after_current", self.stop_after_current),
+        ]
+    }
+}
+
+impl Config {
+    pub fn config_dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rrplay"))
+    }
+
+    pub fn config_file() -> Option<PathBuf> {
+        Config::config_dir().map(|dir| dir.join("config.toml"))
+    }
+
+    /// The plain list of paths used before `config.toml`.
+    fn legacy_file() -> Option<PathBuf> {
+        Config::config_dir().map(|dir| dir.join("config.txt"))
+    }
+
+    /// Reads the config file, creating it on first run.
+    ///
+    /// Never fails: problems are returned as messages for the help desk and defaults are used
+    /// for whatever couldn't be read.
+    pub fn load() -> (Config, Vec<String>) {
+        let Some(file) = Config::config_file() else {
+            return (
+                Config::default(),
+                vec!["Couldn't find the config directory".to_string()],
+            );
+        };
+        let mut errors = Vec::new();
+        let mut config = if file.is_file() {
+            match std::fs::read_to_string(&file) {
+                Ok(content) => match toml::from_str::<Config>(&content) {
+                    Ok(config) => config,
+                    Err(err) => {
+                        errors.push(format!("Couldn't parse {}:", file.display()));
+                        errors.extend(err.to_string().lines().map(|l| l.to_string()));
+                        Config::default()
+                    }
+                },
+                Err(err) => {
+                    errors.push(format!("Couldn't read {}: {err}", file.display()));
+                    Config::default()
+                }
+            }
+        } else {
+            let config = Config::migrate();
+            if let Err(err) = config.write_template(&file) {
+                errors.push(format!("Couldn't write {}: {err}", file.display()));
+            }
+            config
+        };
+        errors.extend(config.validate());
+        (config, errors)
+    }
+
+    /// Builds the first `config.toml`, carrying over the sources from `config.txt` if there is
+    /// one. The old file is kept around as `config.txt.bak`.
+    fn migrate() -> Config {
+        let mut config = Config::default();
+        let Some(legacy) = Config::legacy_file() else {
+            return config;
+        };
+        if let Ok(content) = std::fs::read_to_string(&legacy) {
+            config.sources = content
+                .lines()
+                .map(|l| l.trim())
+                .filter(|l| !l.is_empty())
+                .map(|path| SourceConfig {
+                    path: path.to_string(),
+                    ..Default::default()
+                })
+                .collect();
+            let _ = std::fs::rename(&legacy, legacy.with_extension("txt.bak"));
+        }
+        config
+    }
+
+    /// Writes the first `config.toml`. Only the sources are set, every other value is listed
+    /// commented out with its default, so files don't hold on to defaults that change later.
+    fn write_template(&self, file: &Path) -> std::io::Result<()> {
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut content =
+            "# rrplay config, see the README for what each value does.\n\n".to_string();
+        if !self.sources.is_empty() {
+            #[derive(Serialize)]
+            struct Sources<'a> {
+                sources: &'a [SourceConfig],
+            }
+            let sources = Sources {
+                sources: &self.sources,
+            };
+            content.push_str(&toml::to_string_pretty(&sources).map_err(std::io::Error::other)?);
+            content.push('\n');
+        }
+        let defaults = Config {
+            sources: Vec::new(),
+            ..Config::default()
+        };
+        for line in toml::to_string_pretty(&defaults)
+            .map_err(std::io::Error::other)?
+            .lines()
+            .skip_while(|line| line.is_empty() || line.starts_with("sources ="))
+        {
+            match line {
+                "" => content.push('\n'),
+                _ => content.push_str(&format!("# {line}\n")),
+            }
+        }
+        std::fs::write(file, content)
+    }
+
+    /// Resets values that are out of range to their defaults, returning a message for each.
+    fn validate(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !(self.playback.volume_step > 0.0 && self.playback.volume_step <= 1.0) {
+            errors.push("playback.volume_step must be between 0 and 1".to_string());
+            self.playback.volume_step = PlaybackConfig::default().volume_step;
+        }
+        if self.playback.seek_seconds == 0 {
+            errors.push("playback.seek_seconds must be at least 1".to_string());
+            self.playback.seek_seconds = PlaybackConfig::default().seek_seconds;
+        }
+        if !(self.playback.speed_step > 0.0 && self.playback.speed_step <= 1.0) {
+            errors.push("playback.speed_step must be between 0 and 1".to_string());
+            self.playback.speed_step = PlaybackConfig::default().speed_step;
+        }
+        if !(self.playback.crossfade_seconds > 0.0 && self.playback.crossfade_seconds <= 30.0) {
+            errors.push("playback.crossfade_seconds must be between 0 and 30".to_string());
+            self.playback.crossfade_seconds = PlaybackConfig::default().crossfade_seconds;
+        }
+        if !(0.0..=300.0).contains(&self.playback.sleep_fade_seconds) {
+            errors.push("playback.sleep_fade_seconds must be between 0 and 300".to_string());
+            self.playback.sleep_fade_seconds = PlaybackConfig::default().sleep_fade_seconds;
+        }
+        for (name, gains) in &mut self.eq.presets {
+            if gains
+                .iter()
+                .any(|gain| !(-MAX_GAIN..=MAX_GAIN).contains(gain))
+            {
+                errors.push(format!(
+                    "eq.presets.{name} has gains outside of -{MAX_GAIN} to {MAX_GAIN} dB"
+                ));
+                for gain in gains.iter_mut() {
+                    *gain = gain.clamp(-MAX_GAIN, MAX_GAIN);
+                }
+            }
+        }
+        let presets = EqPresets::new(&self.eq).presets;
+        if self.eq.preset != crate::eq::AUTO && !presets.contains_key(&self.eq.preset) {
+            errors.push(format!("eq.preset: there is no preset {}", self.eq.preset));
+            self.eq.preset = EqConfig::default().preset;
+        }
+        self.eq.genres.retain(|genre, preset| {
+            let exists = presets.contains_key(preset);
+            if !exists {
+                errors.push(format!("eq.genres.{genre}: there is no preset {preset}"));
+            }
+            exists
+        });
+        if !(self.ui.tick_fps > 0.0 && self.ui.tick_fps <= 240.0) {
+            errors.push("ui.tick_fps must be between 0 and 240".to_string());
+            self.ui.tick_fps = UiConfig::default().tick_fps;
+        }
+        if self.ui.notif_duration == 0 {
+            errors.push("ui.notif_duration must be at least 1".to_string());
+            self.ui.notif_duration = UiConfig::default().notif_duration;
+        }
+        for source in &mut self.sources {
+            for patterns in [&mut source.include, &mut source.exclude] {
+                patterns.retain(|pattern| match globset::Glob::new(pattern) {
+                    Ok(_) => true,
+                    Err(err) => {
+                        errors.push(format!("{}: {err}", source.path));
+                        false
+                    }
+                });
+            }
+        }
+        let bindings = self.keys.bindings();
+        let mut conflicting = false;
+        for (name, key) in &bindings {
+            let reserved = if key.is_ascii_digit() {
+                Some("digits seek and pick what to search by")
+            } else if *key == CLEAR_QUEUE_ALT && *name != "clear_queue" {
+                Some("it clears the queue")
+            } else {
+                None
+            };
+            if let Some(reason) = reserved {
+                errors.push(format!("keys.{name} can't be bound to '{key}', {reason}"));
+                conflicting = true;
+            }
+        }
+        for (i, (name, key)) in bindings.iter().enumerate() {
+            if let Some((other, _)) = bindings[..i].iter().find(|(_, k)| k == key) {
+                errors.push(format!(
+                    "keys.{other} and keys.{name} are both bound to '{key}'"
+                ));
+                conflicting = true;
+            }
+        }
+        if conflicting {
+            errors.push("Using the default keybindings instead".to_string());
+            self.keys = Keys::default();
+        }
+        errors
+    }
+}
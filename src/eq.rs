@@ -0,0 +1,303 @@
+use crate::app::Song;
+use crate::config::EqConfig;
+use crate::persist::{read_json, write_json_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// Center frequencies of the bands, in Hz.
+pub const BANDS: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+/// Furthest a band can be turned up or down, in dB.
+pub const MAX_GAIN: f32 = 12.0;
+/// Name under which the preset is picked by genre.
+pub const AUTO: &str = "auto";
+/// Width of the bands, about an octave each.
+const Q: f64 = 1.41;
+
+/// Gain of every band, in dB.
+pub type Gains = [f32; BANDS.len()];
+
+/// Presets that always exist. The config can add more or change these.
+const PRESETS: [(&str, Gains); 8] = [
+    ("flat", [0.0; 10]),
+    (
+        "rock",
+        [4.0, 3.0, -1.0, -2.0, -1.0, 1.0, 3.0, 4.0, 4.0, 4.0],
+    ),
+    (
+        "pop",
+        [-1.0, 1.0, 3.0, 4.0, 3.0, 0.0, -1.0, -1.0, -1.0, -1.0],
+    ),
+    ("jazz", [3.0, 2.0, 1.0, 2.0, -1.0, -1.0, 0.0, 1.0, 2.0, 3.0]),
+    (
+        "classical",
+        [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -2.0, -3.0, -3.0, -4.0],
+    ),
+    (
+        "electronic",
+        [4.0, 3.5, 1.0, 0.0, -2.0, 2.0, 1.0, 1.5, 4.0, 5.0],
+    ),
+    ("bass", [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+    (
+        "vocal",
+        [-2.0, -3.0, -3.0, 1.0, 4.0, 4.0, 3.0, 1.0, 0.0, -2.0],
+    ),
+];
+
+/// Coefficients of a peaking filter, normalised so `a0` is 1.
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl Biquad {
+    /// Boosts or cuts around `freq`, from the Audio EQ Cookbook.
+    fn peaking(freq: f32, gain: f32, sample_rate: u32) -> Self {
+        let a = 10f64.powf(gain as f64 / 40.0);
+        let w0 = 2.0 * std::f64::consts::PI * freq as f64 / sample_rate as f64;
+        let alpha = w0.sin() / (2.0 * Q);
+        let a0 = 1.0 + alpha / a;
+        Biquad {
+            b0: (1.0 + alpha * a) / a0,
+            b1: -2.0 * w0.cos() / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha / a) / a0,
+        }
+    }
+}
+
+/// The last two inputs and outputs of a filter on one channel.
+#[derive(Clone, Copy, Debug, Default)]
+struct History {
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+/// Runs interleaved samples through every band.
+pub struct Equalizer {
+    filters: Vec<Biquad>,
+    /// One per channel for every band.
+    history: Vec<Vec<History>>,
+    channel: usize,
+    sample_rate: u32,
+    gains: Gains,
+}
+
+impl Equalizer {
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        let mut equalizer = Equalizer {
+            filters: Vec::new(),
+            history: vec![vec![History::default(); channels as usize]; BANDS.len()],
+            channel: 0,
+            sample_rate,
+            gains: [0.0; BANDS.len()],
+        };
+        equalizer.set_gains([0.0; BANDS.len()]);
+        equalizer
+    }
+
+    pub fn set_gains(&mut self, gains: Gains) {
+        if gains == self.gains && !self.filters.is_empty() {
+            return;
+        }
+        // Whatever the filters remember from before they were skipped would be heard as a click
+        if self.is_flat() {
+            for history in &mut self.history {
+                history.fill(History::default());
+            }
+        }
+        self.gains = gains;
+        self.filters = BANDS
+            .iter()
+            .zip(gains)
+            .map(|(freq, gain)| Biquad::peaking(*freq, gain, self.sample_rate))
+            .collect();
+    }
+
+    fn is_flat(&self) -> bool {
+        self.gains.iter().all(|gain| *gain == 0.0)
+    }
+
+    /// Takes the next sample. Samples have to come in channel order, starting with the first.
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let channel = self.channel;
+        self.channel = (self.channel + 1) % self.history[0].len();
+        // Flat bands leave the signal as it is, there's no need to run them at all
+        if self.is_flat() {
+            return sample;
+        }
+        let mut x = sample as f64;
+        for (filter, history) in self.filters.iter().zip(&mut self.history) {
+            let h = &mut history[channel];
+            let y = filter.b0 * x + filter.b1 * h.x1 + filter.b2 * h.x2
+                - filter.a1 * h.y1
+                - filter.a2 * h.y2;
+            *h = History {
+                x1: x,
+                x2: h.x1,
+                y1: y,
+                y2: h.y1,
+            };
+            x = y;
+        }
+        x as f32
+    }
+}
+
+/// Presets edited in the equalizer and the one picked there, kept apart from the config so
+/// the app never has to rewrite it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedEq {
+    /// The picked preset or [`AUTO`], `None` keeps the one from the config.
+    pub preset: Option<String>,
+    /// Presets that differ from the built in ones and those in the config.
+    pub presets: BTreeMap<String, Gains>,
+}
+
+impl SavedEq {
+    pub fn eq_file() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rrplay").join("eq.json"))
+    }
+
+    /// Reads the saved presets from disk, starting without any if they are missing or
+    /// unreadable.
+    pub fn load() -> Self {
+        read_json(SavedEq::eq_file()).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        write_json_atomic(SavedEq::eq_file(), self)
+    }
+}
+
+/// The presets along with the one that was picked, shared by every song.
+#[derive(Clone, Debug, Default)]
+pub struct EqPresets {
+    /// Every preset by name, the built in ones together with those from the config and the
+    /// saved ones.
+    pub presets: BTreeMap<String, Gains>,
+    /// The presets as the built in ones and the config have them, to tell what was edited.
+    defaults: BTreeMap<String, Gains>,
+    /// The preset picked, `None` picks one by genre.
+    pub chosen: Option<String>,
+    /// The preset the config picks, so it is only saved when it was changed from that.
+    configured: Option<String>,
+    /// Preset names by lowercase genre.
+    genres: HashMap<String, String>,
+    /// Whether a preset was edited since they were loaded.
+    pub changed: bool,
+}
+
+impl EqPresets {
+    pub fn new(config: &EqConfig) -> Self {
+        let mut presets: BTreeMap<String, Gains> = PRESETS
+            .iter()
+            .map(|(name, gains)| (name.to_string(), *gains))
+            .collect();
+        presets.extend(config.presets.clone());
+        EqPresets {
+            defaults: presets.clone(),
+            presets,
+            chosen: (config.preset != AUTO).then(|| config.preset.clone()),
+            configured: (config.preset != AUTO).then(|| config.preset.clone()),
+            genres: config
+                .genres
+                .iter()
+                .map(|(genre, preset)| (genre.to_lowercase(), preset.clone()))
+                .collect(),
+            changed: false,
+        }
+    }
+
+    /// Puts the presets saved from the equalizer on top of those from the config. A saved
+    /// preset that no longer exists is ignored.
+    pub fn restore(&mut self, saved: SavedEq) {
+        self.presets.extend(saved.presets);
+        match saved.preset {
+            Some(preset) if preset == AUTO => self.chosen = None,
+            Some(preset) if self.presets.contains_key(&preset) => self.chosen = Some(preset),
+            _ => {}
+        }
+    }
+
+    /// The preset a song plays with. Picking by genre goes through the genres from the config
+    /// first, then looks for a preset named after the genre and falls back to "flat".
+    pub fn preset_for(&self, song: Option<&Song>) -> &str {
+        if let Some(chosen) = &self.chosen {
+            return chosen;
+        }
+        let genres = song
+            .and_then(|song| song.genre.as_deref())
+            .unwrap_or_default()
+            .split([';', '/', ','])
+            .map(|genre| genre.trim().to_lowercase());
+        for genre in genres {
+            if let Some(preset) = self.genres.get(&genre) {
+                return preset;
+            }
+            if let Some(preset) = self.presets.keys().find(|p| p.to_lowercase() == genre) {
+                return preset;
+            }
+        }
+        "flat"
+    }
+
+    pub fn gains(&self, preset: &str) -> Gains {
+        self.presets
+            .get(preset)
+            .copied()
+            .unwrap_or([0.0; BANDS.len()])
+    }
+
+    /// Changes one band of a preset, creating it if it doesn't exist yet.
+    pub fn adjust(&mut self, preset: &str, band: usize, delta: f32) {
+        let gains = self
+            .presets
+            .entry(preset.to_string())
+            .or_insert([0.0; BANDS.len()]);
+        gains[band] = (gains[band] + delta).clamp(-MAX_GAIN, MAX_GAIN);
+        self.changed = true;
+    }
+
+    /// Moves on to the next preset, going back to picking by genre after the last one.
+    pub fn cycle(&mut self) {
+        self.chosen = match &self.chosen {
+            None => self.presets.keys().next().cloned(),
+            Some(chosen) => self
+                .presets
+                .range::<String, _>((
+                    std::ops::Bound::Excluded(chosen),
+                    std::ops::Bound::Unbounded,
+                ))
+                .next()
+                .map(|(name, _)| name.clone()),
+        };
+        self.changed = true;
+    }
+
+    /// The picked preset if it isn't the one from the config and every preset that was edited,
+    /// for saving.
+    pub fn to_saved(&mut self) -> SavedEq {
+        self.changed = false;
+        SavedEq {
+            preset: (self.chosen != self.configured)
+                .then(|| self.chosen.clone().unwrap_or(AUTO.to_string())),
+            presets: self
+                .presets
+                .iter()
+                .filter(|(name, gains)| self.defaults.get(*name) != Some(gains))
+                .map(|(name, gains)| (name.clone(), *gains))
+                .collect(),
+        }
+    }
+}
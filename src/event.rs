@@ -1,12 +1,13 @@
+use crate::app::Song;
+use crate::library::{LibraryUpdate, ScanBatch};
+use crate::player::Shuffle;
 use color_eyre::eyre::OptionExt;
 use futures::{FutureExt, StreamExt};
 use ratatui::crossterm::event::Event as CrosstermEvent;
+use std::path::PathBuf;
 use std::time::Duration;
 use tokio::sync::mpsc;
 
-/// The frequency at which tick events are emitted.
-const TICK_FPS: f64 = 30.0;
-
 /// Representation of all possible events.
 #[derive(Clone, Debug)]
 pub enum Event {
@@ -31,7 +32,6 @@ pub enum Event {
 /// You can extend this enum with your own custom events.
 #[derive(Clone, Debug)]
 pub enum AppEvent {
-    InitPopup,
     Quit,
     ClearQueue,
     RefreshResults,
@@ -44,13 +44,77 @@ pub enum AppEvent {
     Pause,
     AddSingle,
     AddAlbum,
+    /// Like [`AppEvent::AddSingle`], but right after the current song.
+    AddSingleNext,
+    /// Like [`AppEvent::AddAlbum`], but right after the current song.
+    AddAlbumNext,
     VolumeUp,
     VolumeDown,
+    SpeedUp,
+    SpeedDown,
+    ResetSpeed,
     Skip,
+    /// Takes the song under the queue cursor out of the queue.
+    RemoveFromQueue,
+    MoveItemUp,
+    MoveItemDown,
+    /// Plays the song under the queue cursor right away.
+    PlayNow,
+    /// Moves the song under the queue cursor right after the current one.
+    PlayNext,
+    Previous,
+    /// Toggles the history panel.
+    History,
+    /// Switches to the next repeat mode.
+    CycleRepeat,
+    ToggleCrossfade,
+    CycleReplayGain,
+    Equalizer,
+    CycleEqPreset,
+    /// Opens or closes the playlist browser.
+    Playlists,
+    /// Asks for a name to save the queue under.
+    NamePlaylist,
+    SavePlaylist,
+    /// Adds the playlist under the browser's cursor to the queue.
+    LoadPlaylist,
+    /// The songs of a playlist, by its file name, ready to be queued.
+    PlaylistLoaded(String, Vec<Song>),
+    /// Sets the A marker, or B, which starts looping between them. Setting B again stops it.
+    LoopStart,
+    LoopEnd,
+    /// Opens or closes the bookmarks of the current song.
+    Bookmarks,
+    /// Asks for a name to bookmark the current position under.
+    NameBookmark,
+    SaveBookmark,
+    JumpToBookmark,
+    RemoveBookmark,
+    /// Moves the sleep timer on to the next number of minutes, or turns it off after the last.
+    CycleSleep,
+    /// Pauses once the current song finishes, or stops doing so.
+    StopAfterCurrent,
+    /// Shuffles the queue that way, or restores its order if it already is.
+    Shuffle(Shuffle),
     MoveForward,
     MoveBackward,
+    /// Jumps to a position in the current song, kept within its length.
+    SeekTo(Duration),
+    /// Runs what was typed in the command line.
+    RunCommand,
     HelpDesk,
+    ScanReport,
     RefreshCache,
+    /// Songs found and files skipped by the background scan, along with its progress so far.
+    ScanBatch(ScanBatch),
+    /// The background scan walked every source.
+    ScanFinished,
+    /// Paths inside the sources were touched on disk.
+    FsChanged(Vec<PathBuf>),
+    /// Touched paths were read again and can be merged into the library.
+    LibraryUpdate(LibraryUpdate),
+    /// Writing the library cache in the background failed.
+    LibrarySaveFailed(String),
 }
 
 /// Terminal event handler.
@@ -62,17 +126,13 @@ pub struct EventHandler {
     receiver: mpsc::UnboundedReceiver<Event>,
 }
 
-impl Default for EventHandler {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl EventHandler {
     /// Constructs a new instance of [`EventHandler`] and spawns a new thread to handle events.
-    pub fn new() -> Self {
+    ///
+    /// Tick events are emitted `tick_fps` times per second.
+    pub fn new(tick_fps: f64) -> Self {
         let (sender, receiver) = mpsc::unbounded_channel();
-        let actor = EventTask::new(sender.clone());
+        let actor = EventTask::new(sender.clone(), tick_fps);
         tokio::spawn(async { actor.run().await });
         Self { sender, receiver }
     }
@@ -93,6 +153,11 @@ impl EventHandler {
             .ok_or_eyre("Failed to receive event")
     }
 
+    /// Returns a sender for emitting events from other tasks or threads, e.g. the library scan.
+    pub fn sender(&self) -> mpsc::UnboundedSender<Event> {
+        self.sender.clone()
+    }
+
     /// Queue an app event to be sent to the event receiver.
     ///
     /// This is useful for sending events to the event handler which will be processed by the next
@@ -108,18 +173,20 @@ impl EventHandler {
 struct EventTask {
     /// Event sender channel.
     sender: mpsc::UnboundedSender<Event>,
+    /// The frequency at which tick events are emitted.
+    tick_fps: f64,
 }
 impl EventTask {
     /// Constructs a new instance of [`EventThread`].
-    fn new(sender: mpsc::UnboundedSender<Event>) -> Self {
-        Self { sender }
+    fn new(sender: mpsc::UnboundedSender<Event>, tick_fps: f64) -> Self {
+        Self { sender, tick_fps }
     }
 
     /// Runs the event thread.
     ///
     /// This function emits tick events at a fixed rate and polls for crossterm events in between.
     async fn run(self) -> color_eyre::Result<()> {
-        let tick_rate = Duration::from_secs_f64(1.0 / TICK_FPS);
+        let tick_rate = Duration::from_secs_f64(1.0 / self.tick_fps);
         let mut reader = crossterm::event::EventStream::new();
         let mut tick = tokio::time::interval(tick_rate);
         loop {
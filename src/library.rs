@@ -0,0 +1,560 @@
+use crate::app::Song;
+use crate::config::SourceConfig;
+use crate::event::{AppEvent, Event};
+use crate::persist::{read_json, write_json_atomic};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use lofty::file::AudioFile;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc::UnboundedSender;
+use walkdir::{DirEntry, WalkDir};
+
+/// Bump whenever the layout of [`Song`] changes so stale caches are rebuilt from scratch.
+const CACHE_VERSION: u32 = 4;
+/// Number of entries the scanner collects before sending them to the app.
+const BATCH_SIZE: usize = 256;
+/// Longest time the scanner goes without reporting progress, even if a batch isn't full yet.
+const REPORT_INTERVAL: Duration = Duration::from_millis(200);
+/// Extensions picked up when a source doesn't list its own.
+const FILE_TYPES: [&str; 10] = [
+    "flac", "m4a", "mp3", "wav", "ogg", "opus", "m4p", "aiff", "3gp", "aac",
+];
+
+/// A probed file together with the filesystem metadata it was probed at.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub modified: SystemTime,
+    pub size: u64,
+    pub song: Song,
+}
+
+/// On-disk index of every song found in the sources.
+///
+/// Loaded at startup so the library is searchable right away, and consulted by the scanner so
+/// only files whose modification time or size changed get probed again.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct LibraryCache {
+    pub version: u32,
+    pub entries: Vec<CacheEntry>,
+}
+
+/// Counters reported by a running scan.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScanProgress {
+    /// Audio files found while walking the sources.
+    pub found: usize,
+    /// Files taken unchanged from the cache.
+    pub cached: usize,
+    /// Files read with lofty.
+    pub probed: usize,
+    /// Files lofty couldn't read and directories that couldn't be walked.
+    pub failed: usize,
+}
+
+/// A file or directory the scanner had to skip.
+#[derive(Clone, Debug)]
+pub struct ScanFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Part of the results of a running scan.
+#[derive(Clone, Debug, Default)]
+pub struct ScanBatch {
+    pub entries: Vec<CacheEntry>,
+    pub failures: Vec<ScanFailure>,
+    /// Progress of the whole scan so far, not just this batch.
+    pub progress: ScanProgress,
+}
+
+/// Result of re-reading paths reported by the [`crate::watch::SourceWatcher`].
+#[derive(Clone, Debug, Default)]
+pub struct LibraryUpdate {
+    /// New or changed files. Replace any entry with the same path.
+    pub added: Vec<CacheEntry>,
+    /// Files or whole directories that no longer exist.
+    pub removed: Vec<PathBuf>,
+    /// Touched files that couldn't be read.
+    pub failures: Vec<ScanFailure>,
+}
+
+/// State of the background scan, kept by the app while it runs.
+pub struct Scan {
+    /// Everything received so far, becomes the new [`LibraryCache`] once the scan finishes.
+    pub entries: Vec<CacheEntry>,
+    pub failures: Vec<ScanFailure>,
+    pub progress: ScanProgress,
+    /// Whether batches go straight into the searchable library. Only done when there was no
+    /// cache to search in the meantime.
+    pub live: bool,
+}
+
+impl LibraryCache {
+    pub fn new(entries: Vec<CacheEntry>) -> Self {
+        LibraryCache {
+            version: CACHE_VERSION,
+            entries,
+        }
+    }
+
+    pub fn cache_file() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("rrplay").join("library.json"))
+    }
+
+    /// Reads the cache from disk, falling back to an empty one if it is missing, unreadable or
+    /// was written by an incompatible version.
+    pub fn load() -> Self {
+        match read_json::<LibraryCache>(LibraryCache::cache_file()) {
+            Some(cache) if cache.version == CACHE_VERSION => cache,
+            _ => LibraryCache::default(),
+        }
+    }
+
+    /// Writes the cache to disk, see [`write_json_atomic`].
+    pub fn save(&self) -> std::io::Result<()> {
+        // Saves run in the background, two of them must not write the same temporary file
+        static SAVING: Mutex<()> = Mutex::new(());
+        let _saving = SAVING.lock().unwrap_or_else(|e| e.into_inner());
+        write_json_atomic(LibraryCache::cache_file(), self)
+    }
+
+    pub fn songs(&self) -> Vec<Song> {
+        self.entries.iter().map(|e| e.song.clone()).collect()
+    }
+
+    /// Entries indexed by file path, for looking up unchanged files during a rescan.
+    pub fn by_path(&self) -> HashMap<&str, &CacheEntry> {
+        self.entries
+            .iter()
+            .map(|e| (e.song.file_path.as_str(), e))
+            .collect()
+    }
+}
+
+/// A source along with its rules for which files to pick up, see [`SourceConfig`].
+#[derive(Clone, Debug)]
+pub struct SourceRules {
+    pub root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    extensions: Vec<String>,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+}
+
+/// Builds a set out of the patterns, skipping invalid ones. Those are reported by the config.
+fn glob_set(patterns: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        if let Ok(glob) = Glob::new(pattern) {
+            builder.add(glob);
+        }
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+impl SourceRules {
+    pub fn new(source: &SourceConfig) -> Self {
+        SourceRules {
+            root: PathBuf::from(&source.path),
+            include: (!source.include.is_empty()).then(|| glob_set(&source.include)),
+            exclude: glob_set(&source.exclude),
+            extensions: if source.extensions.is_empty() {
+                FILE_TYPES.iter().map(|e| e.to_string()).collect()
+            } else {
+                source
+                    .extensions
+                    .iter()
+                    .map(|e| e.trim_start_matches('.').to_string())
+                    .collect()
+            },
+            max_depth: source.max_depth,
+            follow_symlinks: source.follow_symlinks,
+        }
+    }
+
+    /// Walks `dir`, which has to be inside the source, in name order. Excluded directories are
+    /// skipped entirely, files still have to be checked with [`SourceRules::accepts`].
+    fn walk(&self, dir: &Path) -> impl Iterator<Item = walkdir::Result<DirEntry>> + '_ {
+        let mut walker = WalkDir::new(dir)
+            .follow_links(self.follow_symlinks)
+            .sort_by_file_name();
+        if let Some(depth) = self.max_depth {
+            let below_root = dir
+                .strip_prefix(&self.root)
+                .map_or(0, |r| r.components().count());
+            walker = walker.max_depth(depth.saturating_sub(below_root));
+        }
+        walker.into_iter().filter_entry(|entry| {
+            !entry.file_type().is_dir()
+                || entry
+                    .path()
+                    .strip_prefix(&self.root)
+                    .is_ok_and(|r| r.as_os_str().is_empty() || !self.exclude.is_match(r))
+        })
+    }
+
+    /// Whether the file at `path` belongs to the library.
+    pub fn accepts(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        let has_extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)));
+        has_extension
+            && self
+                .max_depth
+                .is_none_or(|depth| relative.components().count() <= depth)
+            && !relative
+                .ancestors()
+                .any(|a| !a.as_os_str().is_empty() && self.exclude.is_match(a))
+            && self
+                .include
+                .as_ref()
+                .is_none_or(|include| include.is_match(relative))
+    }
+}
+
+impl ScanFailure {
+    fn new(path: &Path, error: impl ToString) -> Self {
+        ScanFailure {
+            path: path.display().to_string(),
+            error: error.to_string(),
+        }
+    }
+}
+
+impl From<walkdir::Error> for ScanFailure {
+    fn from(err: walkdir::Error) -> Self {
+        ScanFailure {
+            path: err
+                .path()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            error: err.to_string(),
+        }
+    }
+}
+
+fn cache_entry(path: &Path) -> Result<CacheEntry, ScanFailure> {
+    let metadata = std::fs::metadata(path).map_err(|e| ScanFailure::new(path, e))?;
+    Ok(CacheEntry {
+        modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        size: metadata.len(),
+        song: probe_song(path).map_err(|e| ScanFailure::new(path, e))?,
+    })
+}
+
+/// Reads the encoder delay and the length of the audio, in frames, from an iTunes gapless
+/// tag: ` 00000000 <delay> <padding> <length> ...` in hex.
+fn parse_itunsmpb(value: &str) -> Option<(u32, u64)> {
+    let fields: Vec<&str> = value.split_whitespace().collect();
+    let delay = u32::from_str_radix(fields.get(1)?, 16).ok()?;
+    let frames = u64::from_str_radix(fields.get(3)?, 16).ok()?;
+    (frames > 0).then_some((delay, frames))
+}
+
+/// Reads a ReplayGain value like `-6.48 dB`.
+fn parse_gain(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+    number
+        .trim()
+        .parse()
+        .ok()
+        .filter(|gain: &f32| gain.is_finite())
+}
+
+/// Reads an Opus R128 gain, in 1/256 dB relative to -23 LUFS, as a ReplayGain value which is
+/// relative to -18 LUFS.
+fn parse_r128(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .parse::<i16>()
+        .ok()
+        .map(|gain| gain as f32 / 256.0 + 5.0)
+}
+
+/// Reads the tags and properties of a single file.
+pub fn probe_song(path: &Path) -> lofty::error::Result<Song> {
+    let tagged_file = Probe::open(path)?.read()?;
+    let file_name = path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let file_type = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let properties = tagged_file.properties();
+    // If the "primary" tag doesn't exist, we just grab the first tag we can find
+    let tag = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag());
+    let text = |value: Option<std::borrow::Cow<'_, str>>| {
+        value
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let item = |key: ItemKey| text(tag.and_then(|t| t.get_string(&key)).map(Into::into));
+    // MP3s carry this in their LAME header, which the decoder already honours
+    let gapless = item(ItemKey::Unknown(
+        "----:com.apple.iTunes:iTunSMPB".to_string(),
+    ))
+    .and_then(|value| parse_itunsmpb(&value));
+    let gain = |key: ItemKey, r128: &str| {
+        item(key)
+            .and_then(|value| parse_gain(&value))
+            .or_else(|| item(ItemKey::Unknown(r128.to_string())).and_then(|v| parse_r128(&v)))
+    };
+    let peak = |key: ItemKey| {
+        item(key)
+            .and_then(|value| value.parse::<f32>().ok())
+            .filter(|peak| peak.is_finite())
+    };
+    let song = Song {
+        file_path: path.display().to_string(),
+        file_name,
+        file_type,
+        is_valid: true,
+        title: text(tag.and_then(|t| t.title())),
+        artist: text(tag.and_then(|t| t.artist())),
+        album: text(tag.and_then(|t| t.album())),
+        album_artist: item(ItemKey::AlbumArtist),
+        genre: text(tag.and_then(|t| t.genre())),
+        composer: item(ItemKey::Composer),
+        comment: text(tag.and_then(|t| t.comment())),
+        track_number: tag.and_then(|t| t.track()),
+        track_total: tag.and_then(|t| t.track_total()),
+        disc_number: tag.and_then(|t| t.disk()),
+        disc_total: tag.and_then(|t| t.disk_total()),
+        year: tag.and_then(|t| t.year()),
+        duration: properties.duration(),
+        bitrate: properties.audio_bitrate(),
+        sample_rate: properties.sample_rate(),
+        channels: properties.channels(),
+        bit_depth: properties.bit_depth(),
+        encoder_delay: gapless.map(|(delay, _)| delay),
+        audio_frames: gapless.map(|(_, frames)| frames),
+        track_gain: gain(ItemKey::ReplayGainTrackGain, "R128_TRACK_GAIN"),
+        album_gain: gain(ItemKey::ReplayGainAlbumGain, "R128_ALBUM_GAIN"),
+        track_peak: peak(ItemKey::ReplayGainTrackPeak),
+        album_peak: peak(ItemKey::ReplayGainAlbumPeak),
+    };
+    Ok(song)
+}
+
+/// Outcome of looking at a single file during a scan.
+enum Scanned {
+    Cached(CacheEntry),
+    Probed(CacheEntry),
+    Failed(ScanFailure),
+}
+
+fn scan_file(path: &Path, known: &HashMap<&str, &CacheEntry>) -> Scanned {
+    let metadata = std::fs::metadata(path).ok();
+    let modified = metadata
+        .as_ref()
+        .and_then(|m| m.modified().ok())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let size = metadata.as_ref().map_or(0, |m| m.len());
+
+    let file_path = path.display().to_string();
+    if let Some(cached) = known.get(file_path.as_str())
+        && cached.modified == modified
+        && cached.size == size
+    {
+        return Scanned::Cached((*cached).clone());
+    }
+    match probe_song(path) {
+        Ok(song) => Scanned::Probed(CacheEntry {
+            modified,
+            size,
+            song,
+        }),
+        Err(err) => Scanned::Failed(ScanFailure::new(path, err)),
+    }
+}
+
+/// Walks every source and streams the songs found back to the app.
+///
+/// Meant to run on a blocking thread. The sources are walked first, then the files found are
+/// probed in parallel on `threads` threads, or one per core if it's 0. Files are visited in name
+/// order and results keep that order, so the library comes out the same between runs no matter
+/// how the probing got scheduled.
+///
+/// Files whose modification time and size match an entry of `previous` are taken from it as
+/// is, only new or changed files are probed with lofty. Files that can't be read are skipped
+/// and reported along with the error. Results are sent in [`AppEvent::ScanBatch`]es followed by
+/// a single [`AppEvent::ScanFinished`]. The scan stops early if the app goes away.
+pub fn scan(
+    sources: Vec<SourceRules>,
+    previous: LibraryCache,
+    threads: usize,
+    sender: UnboundedSender<Event>,
+) {
+    let known = previous.by_path();
+    let mut batch = ScanBatch::default();
+    let mut last_report = Instant::now();
+    let mut report = |batch: &mut ScanBatch, force: bool| {
+        if force || batch.entries.len() >= BATCH_SIZE || last_report.elapsed() >= REPORT_INTERVAL {
+            let progress = batch.progress;
+            let full = std::mem::take(batch);
+            batch.progress = progress;
+            let _ = sender.send(Event::App(AppEvent::ScanBatch(full)));
+            last_report = Instant::now();
+        }
+    };
+
+    let mut files = Vec::new();
+    for source in &sources {
+        for entry in source.walk(&source.root) {
+            if sender.is_closed() {
+                return;
+            }
+            match entry {
+                Ok(entry) if source.accepts(entry.path()) => {
+                    batch.progress.found += 1;
+                    files.push(entry.into_path());
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    batch.progress.failed += 1;
+                    batch.failures.push(err.into());
+                }
+            }
+            report(&mut batch, false);
+        }
+    }
+
+    // Without a pool of its own, the scan shares the global one
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .ok();
+    for chunk in files.chunks(BATCH_SIZE) {
+        if sender.is_closed() {
+            return;
+        }
+        let probe = || chunk.par_iter().map(|p| scan_file(p, &known)).collect();
+        let scanned: Vec<Scanned> = match &pool {
+            Some(pool) => pool.install(probe),
+            None => probe(),
+        };
+        for outcome in scanned {
+            match outcome {
+                Scanned::Cached(entry) => {
+                    batch.progress.cached += 1;
+                    batch.entries.push(entry);
+                }
+                Scanned::Probed(entry) => {
+                    batch.progress.probed += 1;
+                    batch.entries.push(entry);
+                }
+                Scanned::Failed(failure) => {
+                    batch.progress.failed += 1;
+                    batch.failures.push(failure);
+                }
+            }
+        }
+        report(&mut batch, false);
+    }
+    report(&mut batch, true);
+    let _ = sender.send(Event::App(AppEvent::ScanFinished));
+}
+
+/// Re-reads paths that changed on disk and sends the result back as an
+/// [`AppEvent::LibraryUpdate`].
+///
+/// Meant to run on a blocking thread. Paths that no longer exist are reported as removed,
+/// directories that do are walked for audio files. Only files the rules of their source accept
+/// are read.
+pub fn rescan_paths(
+    paths: Vec<PathBuf>,
+    sources: Vec<SourceRules>,
+    sender: UnboundedSender<Event>,
+) {
+    let mut update = LibraryUpdate::default();
+    let read = |path: &Path, update: &mut LibraryUpdate| match cache_entry(path) {
+        Ok(entry) => update.added.push(entry),
+        Err(failure) => update.failures.push(failure),
+    };
+    for path in paths {
+        let Some(source) = sources.iter().find(|s| path.starts_with(&s.root)) else {
+            continue;
+        };
+        if !path.exists() {
+            update.removed.push(path);
+        } else if path.is_dir() {
+            for entry in source.walk(&path) {
+                match entry {
+                    Ok(entry) if source.accepts(entry.path()) => read(entry.path(), &mut update),
+                    Ok(_) => {}
+                    Err(err) => update.failures.push(err.into()),
+                }
+            }
+        } else if source.accepts(&path) {
+            read(&path, &mut update);
+        }
+    }
+    let _ = sender.send(Event::App(AppEvent::LibraryUpdate(update)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn itunsmpb_reads_delay_and_length() {
+        // As written by iTunes for an AAC file: delay 2112, padding 212, 3115884 frames
+        let value = " 00000000 00000840 000000D4 00000000002F8B6C 00000000 002A3B5A \
+            00000000 00000000 00000000 00000000 00000000 00000000";
+        assert_eq!(parse_itunsmpb(value), Some((2112, 3_115_884)));
+    }
+
+    #[test]
+    fn itunsmpb_rejects_incomplete_tags() {
+        assert_eq!(parse_itunsmpb(" 00000000 00000840 000000D4"), None);
+        assert_eq!(
+            parse_itunsmpb(" 00000000 00000840 000000D4 0000000000000000"),
+            None
+        );
+        assert_eq!(
+            parse_itunsmpb(" 00000000 0000084G 000000D4 00000000002F8B6C"),
+            None
+        );
+        assert_eq!(parse_itunsmpb(""), None);
+    }
+
+    #[test]
+    fn replaygain_reads_decibels() {
+        assert_eq!(parse_gain("-6.48 dB"), Some(-6.48));
+        assert_eq!(parse_gain("+2.10 dB"), Some(2.10));
+        assert_eq!(parse_gain(" -0.53 db "), Some(-0.53));
+        assert_eq!(parse_gain("-11.2"), Some(-11.2));
+        assert_eq!(parse_gain("loud dB"), None);
+        assert_eq!(parse_gain("NaN dB"), None);
+    }
+
+    #[test]
+    fn r128_is_moved_to_the_replaygain_reference() {
+        // -23 LUFS is 5 dB below ReplayGain's -18 LUFS
+        assert_eq!(parse_r128("0"), Some(5.0));
+        assert_eq!(parse_r128("-1280"), Some(0.0));
+        assert_eq!(parse_r128("-2624"), Some(-5.25));
+        assert_eq!(parse_r128("384"), Some(6.5));
+        assert_eq!(parse_r128("-6.5"), None);
+        assert_eq!(parse_r128("40000"), None);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::app::App;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use crossterm::execute;
 use crossterm::terminal::EnterAlternateScreen;
 use crossterm::terminal::LeaveAlternateScreen;
@@ -10,8 +11,18 @@ use std::panic::set_hook;
 use std::panic::take_hook;
 
 pub mod app;
+pub mod bookmarks;
+pub mod config;
+pub mod eq;
 pub mod event;
+pub mod library;
+pub mod persist;
+pub mod player;
+pub mod playlist;
+pub mod state;
+pub mod stretch;
 pub mod ui;
+pub mod watch;
 pub mod widgets;
 
 #[tokio::main]
@@ -19,7 +30,10 @@ async fn main() -> color_eyre::Result<()> {
     init_panic_hook();
     color_eyre::install()?;
     let terminal = ratatui::init();
+    // Lets the progress bar be clicked on
+    execute!(stdout(), EnableMouseCapture)?;
     let result = App::default().run(terminal).await;
+    let _ = execute!(stdout(), DisableMouseCapture);
     ratatui::restore();
     result
 }
@@ -41,6 +55,6 @@ pub fn init_tui() -> color_eyre::Result<Terminal<impl Backend>> {
 
 pub fn restore_tui() -> color_eyre::Result<()> {
     disable_raw_mode()?;
-    execute!(stdout(), LeaveAlternateScreen)?;
+    execute!(stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
     Ok(())
 }
@@ -0,0 +1,24 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::PathBuf;
+
+/// Reads a JSON file, `None` if there is no file or it is missing or unreadable.
+pub fn read_json<T: DeserializeOwned>(file: Option<PathBuf>) -> Option<T> {
+    let bytes = std::fs::read(file?).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Writes a JSON file, creating its directory. It is written next to the file first and renamed
+/// over it, so a crash mid-write never leaves a truncated file behind. Without a file there is
+/// nothing to do.
+pub fn write_json_atomic<T: Serialize>(file: Option<PathBuf>, value: &T) -> std::io::Result<()> {
+    let Some(file) = file else {
+        return Ok(());
+    };
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = file.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec(value)?)?;
+    std::fs::rename(tmp, file)
+}
@@ -0,0 +1,1064 @@
+use crate::app::Song;
+use crate::eq::{EqPresets, Equalizer, Gains, SavedEq};
+use crate::stretch::Stretcher;
+use rand::seq::SliceRandom;
+use rodio::source::UniformSourceIterator;
+use rodio::{Decoder, OutputStream, Sink, Source};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::time::Duration;
+
+/// Every track is converted to this before it reaches the output, so the engine can move from
+/// one to the next without reopening the stream. The [`Stretcher`] relies on there being two
+/// channels.
+const CHANNELS: u16 = 2;
+const SAMPLE_RATE: u32 = 44_100;
+/// How long before the end of the current track the next one gets opened.
+const PRELOAD_TIME: Duration = Duration::from_secs(10);
+/// Samples played between checks for new commands, a multiple of [`CHANNELS`] so commands are
+/// always applied on a frame boundary.
+const COMMAND_INTERVAL: usize = 512;
+/// How many played songs the history keeps.
+const HISTORY_LEN: usize = 100;
+/// Going back after this much of a song played restarts it instead.
+const RESTART_TIME: Duration = Duration::from_secs(3);
+/// Slowest and fastest songs can play.
+pub const MIN_SPEED: f32 = 0.5;
+pub const MAX_SPEED: f32 = 3.0;
+/// Highest level the limiter lets through.
+const LIMIT: f32 = 0.98;
+/// How much the limiter lets go per sample once the signal is back under [`LIMIT`], about a
+/// second from full reduction.
+const LIMITER_RELEASE: f32 = 1.0 / (SAMPLE_RATE * CHANNELS as u32) as f32;
+
+type TrackSource = UniformSourceIterator<Gapless<Decoder<BufReader<File>>>, f32>;
+
+/// Drops the encoder delay at the start of a track and the padding at its end, which would
+/// otherwise be heard as a gap between tracks.
+struct Gapless<S> {
+    inner: S,
+    /// Encoder delay, in frames.
+    delay: u64,
+    /// Length of the audio without delay and padding, in frames.
+    frames: Option<u64>,
+    /// Samples left to drop before the audio starts.
+    skip: u64,
+    /// Samples left before the padding starts.
+    remaining: Option<u64>,
+}
+
+impl<S: Source<Item = i16>> Gapless<S> {
+    fn new(inner: S, delay: u32, frames: Option<u64>) -> Self {
+        let channels = inner.channels() as u64;
+        Gapless {
+            inner,
+            delay: delay as u64,
+            frames,
+            skip: delay as u64 * channels,
+            remaining: frames.map(|f| f * channels),
+        }
+    }
+}
+
+impl<S: Source<Item = i16>> Iterator for Gapless<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        while self.skip > 0 {
+            self.inner.next()?;
+            self.skip -= 1;
+        }
+        if let Some(remaining) = &mut self.remaining {
+            if *remaining == 0 {
+                return None;
+            }
+            *remaining -= 1;
+        }
+        self.inner.next()
+    }
+}
+
+impl<S: Source<Item = i16>> Source for Gapless<S> {
+    // Samples are dropped without regard for the decoder's frames, so the whole track has to be
+    // treated as one
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        match self.frames {
+            Some(frames) => Some(Duration::from_secs_f64(
+                frames as f64 / self.inner.sample_rate() as f64,
+            )),
+            None => self.inner.total_duration(),
+        }
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        let rate = self.inner.sample_rate() as f64;
+        let delay = Duration::from_secs_f64(self.delay as f64 / rate);
+        self.inner.try_seek(pos + delay)?;
+        let played = (pos.as_secs_f64() * rate) as u64;
+        let channels = self.inner.channels() as u64;
+        self.skip = 0;
+        self.remaining = self
+            .frames
+            .map(|frames| frames.saturating_sub(played) * channels);
+        Ok(())
+    }
+}
+
+/// Converts a duration into a number of samples at the output format.
+fn to_samples(duration: Duration) -> u64 {
+    (duration.as_secs_f64() * SAMPLE_RATE as f64) as u64 * CHANNELS as u64
+}
+
+/// An opened file, ready to be decoded.
+struct Track {
+    id: u64,
+    source: TrackSource,
+    /// Length in samples, if known.
+    length: Option<u64>,
+    /// Samples this track overlaps with the end of the one before it, 0 for no crossfade.
+    crossfade: u64,
+    /// Linear ReplayGain factor.
+    gain: f32,
+    /// Equalizer settings the track plays with.
+    eq: Gains,
+}
+
+enum Command {
+    /// Starts playing a track right away, dropping the current and next ones.
+    Play(Track),
+    /// Sets the track to continue with once the current one ends.
+    SetNext(Option<Track>),
+    Seek(Duration),
+    /// Changes the gain of the current track.
+    Gain(f32),
+    /// Changes the equalizer settings of the current track.
+    Equalizer(Gains),
+    Speed {
+        speed: f32,
+        keep_pitch: bool,
+    },
+    /// Jumps back to the start whenever the current track reaches the end, until it changes.
+    Loop(Option<(Duration, Duration)>),
+    Skip,
+    Stop,
+}
+
+/// Sent back by the engine when a track ends or is skipped, along with the track that took its
+/// place, if any.
+struct Ended {
+    id: u64,
+    next: Option<u64>,
+}
+
+/// What happens when a song ends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Repeat {
+    /// The song leaves the queue.
+    #[default]
+    Off,
+    /// The song plays again.
+    One,
+    /// The song goes to the back of the queue.
+    Queue,
+}
+
+impl Repeat {
+    /// The mode the repeat key switches to.
+    pub fn cycle(self) -> Self {
+        match self {
+            Repeat::Off => Repeat::One,
+            Repeat::One => Repeat::Queue,
+            Repeat::Queue => Repeat::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Repeat::Off => "Repeat off",
+            Repeat::One => "Repeat one",
+            Repeat::Queue => "Repeat queue",
+        }
+    }
+}
+
+/// How the upcoming songs are ordered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Shuffle {
+    /// In the order they were added.
+    #[default]
+    Off,
+    Tracks,
+    /// Albums in random order, each one played through in track order.
+    Albums,
+}
+
+impl Shuffle {
+    pub fn label(self) -> &'static str {
+        match self {
+            Shuffle::Off => "Shuffle off",
+            Shuffle::Tracks => "Shuffle",
+            Shuffle::Albums => "Album shuffle",
+        }
+    }
+}
+
+/// Which ReplayGain values playback is normalised with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayGain {
+    Off,
+    /// Every song on its own, for mixed queues.
+    #[default]
+    Track,
+    /// Whole albums at once, keeping the loudness differences between their songs.
+    Album,
+}
+
+impl ReplayGain {
+    /// The mode the ReplayGain key switches to.
+    pub fn cycle(self) -> Self {
+        match self {
+            ReplayGain::Off => ReplayGain::Track,
+            ReplayGain::Track => ReplayGain::Album,
+            ReplayGain::Album => ReplayGain::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReplayGain::Off => "ReplayGain off",
+            ReplayGain::Track => "Track gain",
+            ReplayGain::Album => "Album gain",
+        }
+    }
+
+    /// The factor to scale a song by. Falls back to the other gain when a song only has one,
+    /// and is lowered so the song's peak doesn't clip.
+    fn gain(self, song: &Song) -> f32 {
+        let track = song.track_gain.map(|gain| (gain, song.track_peak));
+        let album = song.album_gain.map(|gain| (gain, song.album_peak));
+        let (gain, peak) = match self {
+            ReplayGain::Off => return 1.0,
+            ReplayGain::Track => track.or(album),
+            ReplayGain::Album => album.or(track),
+        }
+        .unwrap_or((0.0, None));
+        let gain = 10f32.powf(gain / 20.0);
+        match peak {
+            Some(peak) if peak > 0.0 => gain.min(1.0 / peak),
+            _ => gain,
+        }
+    }
+}
+
+/// Shuffles songs album by album. Songs without an album tag count as an album of their own.
+fn shuffle_albums(songs: &mut [Song]) {
+    let mut albums: Vec<Vec<Song>> = Vec::new();
+    for song in songs.iter() {
+        match albums
+            .iter_mut()
+            .find(|album| song.album.is_some() && album[0].album_key() == song.album_key())
+        {
+            Some(album) => album.push(song.clone()),
+            None => albums.push(vec![song.clone()]),
+        }
+    }
+    albums.shuffle(&mut rand::rng());
+    for album in &mut albums {
+        album.sort_by(|a, b| a.track_order().cmp(&b.track_order()));
+    }
+    for (slot, song) in songs.iter_mut().zip(albums.into_iter().flatten()) {
+        *slot = song;
+    }
+}
+
+/// The single source the sink ever plays. It decodes the current track and moves on to the next
+/// one when it runs out, playing silence when there's nothing to play.
+struct Engine {
+    current: Option<Track>,
+    next: Option<Track>,
+    commands: Receiver<Command>,
+    ended: Sender<Ended>,
+    /// Samples played of the current track, shared with the [`Player`].
+    position: Arc<AtomicU64>,
+    played: u64,
+    countdown: usize,
+    /// The previous track, still playing underneath while the current one fades in.
+    fading: Option<Track>,
+    faded: u64,
+    /// Reduction the limiter currently applies, 1 for none.
+    limiter: f32,
+    eq: Equalizer,
+    stretcher: Stretcher,
+    /// Second half of the frame that came out of the stretcher.
+    right: Option<f32>,
+    /// Where the loop starts, and where it ends in samples.
+    ab_loop: Option<(Duration, u64)>,
+}
+
+impl Engine {
+    fn handle_commands(&mut self) {
+        while let Ok(command) = self.commands.try_recv() {
+            match command {
+                Command::Play(track) => {
+                    self.eq.set_gains(track.eq);
+                    self.current = Some(track);
+                    self.next = None;
+                    self.fading = None;
+                    self.played = 0;
+                    self.stretcher.reset();
+                    self.ab_loop = None;
+                }
+                Command::SetNext(track) => self.next = track,
+                Command::Seek(pos) => {
+                    if let Some(track) = &mut self.current
+                        && track.source.try_seek(pos).is_ok()
+                    {
+                        self.played = to_samples(pos);
+                        self.fading = None;
+                        self.stretcher.reset();
+                    }
+                }
+                Command::Gain(gain) => {
+                    if let Some(track) = &mut self.current {
+                        track.gain = gain;
+                    }
+                }
+                Command::Equalizer(gains) => {
+                    if let Some(track) = &mut self.current {
+                        track.eq = gains;
+                        self.eq.set_gains(gains);
+                    }
+                }
+                Command::Speed { speed, keep_pitch } => {
+                    self.stretcher.set_speed(speed, keep_pitch);
+                }
+                Command::Loop(ab_loop) => {
+                    self.ab_loop = ab_loop.map(|(start, end)| (start, to_samples(end)));
+                }
+                Command::Skip => self.advance(false),
+                Command::Stop => {
+                    self.current = None;
+                    self.next = None;
+                    self.fading = None;
+                    self.played = 0;
+                    self.stretcher.reset();
+                    self.ab_loop = None;
+                }
+            }
+        }
+        self.position.store(self.played, Ordering::Relaxed);
+    }
+
+    /// Continues with the next track. The current one is dropped, or keeps playing underneath
+    /// if the next one fades in over it.
+    fn advance(&mut self, crossfade: bool) {
+        if let Some(track) = self.current.take() {
+            self.current = self.next.take();
+            if let Some(next) = &self.current {
+                self.eq.set_gains(next.eq);
+            }
+            self.ab_loop = None;
+            self.played = 0;
+            let _ = self.ended.send(Ended {
+                id: track.id,
+                next: self.current.as_ref().map(|t| t.id),
+            });
+            self.faded = 0;
+            self.fading = crossfade.then_some(track);
+        }
+    }
+
+    /// Whether the current track got close enough to its end for the next one to fade in.
+    fn should_crossfade(&self) -> bool {
+        let (Some(current), Some(next), None) = (&self.current, &self.next, self.ab_loop) else {
+            return false;
+        };
+        // Starting on a frame boundary keeps the channels of both tracks lined up
+        next.crossfade > 0
+            && self.played.is_multiple_of(CHANNELS as u64)
+            && current
+                .length
+                .is_some_and(|length| self.played + next.crossfade >= length)
+    }
+
+    /// Mixes in what's left of the previous track, with equal power fades so the volume doesn't
+    /// dip halfway through.
+    fn mix(&mut self, sample: f32) -> f32 {
+        let (Some(fading), Some(current)) = (&mut self.fading, &self.current) else {
+            return sample;
+        };
+        let progress = (self.faded as f32 / current.crossfade.max(1) as f32).min(1.0);
+        self.faded += 1;
+        let angle = progress * std::f32::consts::FRAC_PI_2;
+        match fading.source.next() {
+            Some(old) if self.faded < current.crossfade => {
+                sample * angle.sin() + old * fading.gain * angle.cos()
+            }
+            _ => {
+                self.fading = None;
+                sample
+            }
+        }
+    }
+
+    /// Decodes the next sample at normal speed, moving on to the next track when needed.
+    fn render(&mut self) -> f32 {
+        if self.countdown == 0 {
+            self.handle_commands();
+            self.countdown = COMMAND_INTERVAL;
+        }
+        self.countdown -= 1;
+        loop {
+            if let Some((start, end)) = self.ab_loop
+                && self.played >= end
+                && self.played.is_multiple_of(CHANNELS as u64)
+                && let Some(track) = &mut self.current
+            {
+                if track.source.try_seek(start).is_ok() {
+                    self.played = to_samples(start);
+                } else {
+                    self.ab_loop = None;
+                }
+            }
+            if self.should_crossfade() {
+                self.advance(true);
+            }
+            let Some(track) = &mut self.current else {
+                return 0.0;
+            };
+            if let Some(sample) = track.source.next() {
+                let sample = sample * track.gain;
+                self.played += 1;
+                return self.mix(sample);
+            }
+            self.advance(false);
+        }
+    }
+
+    /// Keeps samples pushed up by ReplayGain from clipping. Loud samples are cut right away, the
+    /// reduction is let go of slowly so it isn't heard as distortion.
+    fn limit(&mut self, sample: f32) -> f32 {
+        let level = sample.abs();
+        if level * self.limiter > LIMIT {
+            self.limiter = LIMIT / level;
+        } else {
+            self.limiter = (self.limiter + LIMITER_RELEASE).min(1.0);
+        }
+        sample * self.limiter
+    }
+}
+
+impl Iterator for Engine {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = match self.right.take() {
+            Some(sample) => sample,
+            None => {
+                let frame = loop {
+                    if let Some(frame) = self.stretcher.pop() {
+                        break frame;
+                    }
+                    let frame = [self.render(), self.render()];
+                    self.stretcher.push(frame);
+                };
+                self.right = Some(frame[1]);
+                frame[0]
+            }
+        };
+        // Even silence goes through the equalizer, it has to see every sample to know which
+        // channel comes next
+        let sample = self.eq.process(sample);
+        Some(self.limit(sample))
+    }
+}
+
+impl Source for Engine {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Plays the queue.
+///
+/// The queue is plain data, only the current track and the one after it are ever opened. The
+/// next one is opened shortly before it's needed, see [`PRELOAD_TIME`].
+pub struct Player {
+    /// Songs to play, the first one is the one playing.
+    pub queue: Vec<Song>,
+    /// Songs that were played, most recent first.
+    pub history: VecDeque<Song>,
+    repeat: Repeat,
+    shuffle: Shuffle,
+    /// The upcoming songs as they were before shuffling.
+    unshuffled: Vec<Song>,
+    sink: Sink,
+    _stream: OutputStream,
+    commands: Sender<Command>,
+    ended: Receiver<Ended>,
+    position: Arc<AtomicU64>,
+    last_id: u64,
+    /// Track the engine is playing, always `queue[0]`.
+    current: Option<u64>,
+    /// Track lined up after the current one, see [`Player::next_index`].
+    next: Option<u64>,
+    /// Where to start the next track that gets played, for restoring a session.
+    start_at: Option<Duration>,
+    /// How long songs overlap, `None` when crossfade is off.
+    crossfade: Option<Duration>,
+    /// Keeps songs of the same album from crossfading into each other.
+    album_mode: bool,
+    replaygain: ReplayGain,
+    eq: EqPresets,
+    speed: f32,
+    /// Markers on the current song, it loops between them once both are set.
+    loop_start: Option<Duration>,
+    loop_end: Option<Duration>,
+    /// How many songs are left to finish before pausing, counting the current one.
+    stop_after: Option<usize>,
+}
+
+impl Player {
+    pub fn new() -> color_eyre::Result<Self> {
+        let (stream, stream_handle) = OutputStream::try_default()?;
+        let sink = Sink::try_new(&stream_handle)?;
+        let (commands, command_receiver) = channel();
+        let (ended_sender, ended) = channel();
+        let position = Arc::new(AtomicU64::new(0));
+        sink.append(Engine {
+            current: None,
+            next: None,
+            commands: command_receiver,
+            ended: ended_sender,
+            position: position.clone(),
+            played: 0,
+            countdown: 0,
+            fading: None,
+            faded: 0,
+            limiter: 1.0,
+            eq: Equalizer::new(CHANNELS, SAMPLE_RATE),
+            stretcher: Stretcher::new(),
+            right: None,
+            ab_loop: None,
+        });
+        Ok(Player {
+            queue: Vec::new(),
+            history: VecDeque::new(),
+            repeat: Repeat::Off,
+            shuffle: Shuffle::Off,
+            unshuffled: Vec::new(),
+            sink,
+            _stream: stream,
+            commands,
+            ended,
+            position,
+            last_id: 0,
+            current: None,
+            next: None,
+            start_at: None,
+            crossfade: None,
+            album_mode: true,
+            replaygain: ReplayGain::default(),
+            eq: EqPresets::default(),
+            speed: 1.0,
+            loop_start: None,
+            loop_end: None,
+            stop_after: None,
+        })
+    }
+
+    /// Opens a song. `crossfade` is the song playing before it, if it should fade into it.
+    fn open(&mut self, song: &Song, crossfade: Option<&Song>) -> Option<Track> {
+        if !song.is_valid {
+            return None;
+        }
+        let file = File::open(&song.file_path).ok()?;
+        let decoder = Decoder::new(BufReader::new(file)).ok()?;
+        self.last_id += 1;
+        Some(Track {
+            id: self.last_id,
+            source: UniformSourceIterator::new(
+                Gapless::new(decoder, song.encoder_delay.unwrap_or(0), song.audio_frames),
+                CHANNELS,
+                SAMPLE_RATE,
+            ),
+            length: (!song.duration.is_zero()).then(|| to_samples(song.duration)),
+            crossfade: match (self.crossfade, crossfade) {
+                (Some(time), Some(previous))
+                    if !(self.album_mode
+                        && previous.album.is_some()
+                        && previous.album_key() == song.album_key()) =>
+                {
+                    to_samples(time)
+                }
+                _ => 0,
+            },
+            gain: self.replaygain.gain(song),
+            eq: self.eq.gains(self.eq.preset_for(Some(song))),
+        })
+    }
+
+    fn send(&self, command: Command) {
+        let _ = self.commands.send(command);
+    }
+
+    /// Where in the queue the song that plays after the current one is.
+    fn next_index(&self) -> Option<usize> {
+        match self.repeat {
+            Repeat::Off => (self.queue.len() > 1).then_some(1),
+            Repeat::One => Some(0),
+            Repeat::Queue => Some(if self.queue.len() > 1 { 1 } else { 0 }),
+        }
+    }
+
+    /// Drops the lined up track, for when the song after the current one changes.
+    fn invalidate_next(&mut self) {
+        if self.next.take().is_some() {
+            self.send(Command::SetNext(None));
+        }
+    }
+
+    /// Drops the tracks the engine has, the next update starts `queue[0]` over whatever is
+    /// playing.
+    fn reload(&mut self) {
+        self.current = None;
+        self.next = None;
+        self.loop_start = None;
+        self.loop_end = None;
+        if self.queue.is_empty() {
+            self.position.store(0, Ordering::Relaxed);
+            self.send(Command::Stop);
+        }
+    }
+
+    /// Moves on from the current song according to the repeat mode.
+    fn finish_current(&mut self) {
+        self.loop_start = None;
+        self.loop_end = None;
+        if self.queue.is_empty() {
+            return;
+        }
+        if self.repeat != Repeat::One {
+            let song = self.queue.remove(0);
+            if self.repeat == Repeat::Queue {
+                self.queue.push(song.clone());
+            }
+            self.history.push_front(song);
+            self.history.truncate(HISTORY_LEN);
+        }
+    }
+
+    /// Follows what the engine played and opens tracks as they're needed. Called every tick.
+    ///
+    /// Returns the songs that couldn't be opened, they're taken out of the queue.
+    pub fn update(&mut self) -> Vec<Song> {
+        while let Ok(ended) = self.ended.try_recv() {
+            // Tracks replaced by a command don't count
+            if Some(ended.id) != self.current {
+                continue;
+            }
+            self.finish_current();
+            match self.stop_after {
+                Some(n) if n > 1 => self.stop_after = Some(n - 1),
+                Some(_) => {
+                    self.stop_after = None;
+                    self.pause();
+                }
+                None => {}
+            }
+            if ended.next.is_some() && ended.next == self.next {
+                self.current = self.next.take();
+            } else {
+                self.current = None;
+                self.next = None;
+            }
+        }
+
+        let mut failed = Vec::new();
+        while self.current.is_none() && !self.queue.is_empty() {
+            match self.open(&self.queue[0].clone(), None) {
+                Some(track) => {
+                    self.current = Some(track.id);
+                    self.next = None;
+                    self.position.store(0, Ordering::Relaxed);
+                    self.send(Command::Play(track));
+                    if let Some(pos) = self.start_at.take() {
+                        self.seek(pos);
+                    }
+                }
+                None => {
+                    self.start_at = None;
+                    failed.push(self.queue.remove(0));
+                }
+            }
+        }
+        // Nothing is lined up after the last song before stopping, the engine goes quiet instead
+        if self.current.is_some()
+            && self.next.is_none()
+            && self.stop_after != Some(1)
+            && let Some(index) = self.next_index()
+        {
+            let remaining = self.queue[0].duration.saturating_sub(self.position());
+            if remaining < PRELOAD_TIME + self.crossfade.unwrap_or_default() {
+                let current = self.queue[0].clone();
+                match self.open(&self.queue[index].clone(), Some(&current)) {
+                    Some(track) => {
+                        self.next = Some(track.id);
+                        self.send(Command::SetNext(Some(track)));
+                    }
+                    // The current song is playing fine, it gets dropped if it fails to restart
+                    None if index == 0 => {}
+                    None => failed.push(self.queue.remove(index)),
+                }
+            }
+        }
+        failed
+    }
+
+    pub fn push(&mut self, songs: impl IntoIterator<Item = Song>) {
+        let next = self.next_index();
+        self.queue.extend(songs);
+        if self.next_index() != next {
+            self.invalidate_next();
+        }
+    }
+
+    /// Inserts songs right after the current one, keeping their order.
+    pub fn insert_next(&mut self, songs: impl IntoIterator<Item = Song>) {
+        let at = self.queue.len().min(1);
+        self.queue.splice(at..at, songs);
+        self.invalidate_next();
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.reload();
+    }
+
+    /// Moves on to the next song. Repeating a single song doesn't stop it from being skipped.
+    pub fn skip(&mut self) {
+        if self.current.is_none() {
+            return;
+        }
+        if self.repeat == Repeat::One {
+            let song = self.queue.remove(0);
+            self.history.push_front(song);
+            self.history.truncate(HISTORY_LEN);
+            self.reload();
+        } else {
+            self.send(Command::Skip);
+        }
+    }
+
+    /// Restarts the current song, or goes back to the previous one if it only just started.
+    pub fn previous(&mut self) {
+        if self.position() > RESTART_TIME {
+            self.seek(Duration::ZERO);
+        } else if let Some(song) = self.history.pop_front() {
+            // With the whole queue repeating, the previous song was put at the back of it. Songs
+            // that finished before repeat was turned on aren't there.
+            if self.repeat == Repeat::Queue
+                && self
+                    .queue
+                    .last()
+                    .is_some_and(|last| last.file_path == song.file_path)
+            {
+                self.queue.pop();
+            }
+            self.queue.insert(0, song);
+            self.reload();
+        } else {
+            self.seek(Duration::ZERO);
+        }
+    }
+
+    /// Takes a song out of the queue. Removing the current one moves on to the next.
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.queue.len() {
+            return;
+        }
+        self.queue.remove(index);
+        if index == 0 {
+            self.reload();
+        } else {
+            self.invalidate_next();
+        }
+    }
+
+    /// Swaps two upcoming songs, the current one can't be moved. Returns whether they were
+    /// swapped.
+    pub fn swap(&mut self, a: usize, b: usize) -> bool {
+        let len = self.queue.len();
+        if a == 0 || b == 0 || a >= len || b >= len {
+            return false;
+        }
+        self.queue.swap(a, b);
+        self.invalidate_next();
+        true
+    }
+
+    /// Moves a song right after the current one.
+    pub fn play_next(&mut self, index: usize) {
+        if index <= 1 || index >= self.queue.len() {
+            return;
+        }
+        let song = self.queue.remove(index);
+        self.queue.insert(1, song);
+        self.invalidate_next();
+    }
+
+    /// Interrupts the current song to play another one from the queue.
+    pub fn play_now(&mut self, index: usize) {
+        if index == 0 || index >= self.queue.len() {
+            return;
+        }
+        let song = self.queue.remove(index);
+        let interrupted = std::mem::replace(&mut self.queue[0], song);
+        self.history.push_front(interrupted);
+        self.history.truncate(HISTORY_LEN);
+        self.reload();
+    }
+
+    pub fn crossfade(&self) -> Option<Duration> {
+        self.crossfade
+    }
+
+    pub fn set_crossfade(&mut self, crossfade: Option<Duration>) {
+        self.crossfade = crossfade;
+        self.invalidate_next();
+    }
+
+    pub fn set_album_mode(&mut self, album_mode: bool) {
+        self.album_mode = album_mode;
+        self.invalidate_next();
+    }
+
+    pub fn replaygain(&self) -> ReplayGain {
+        self.replaygain
+    }
+
+    /// Switches the ReplayGain mode, the current song is turned up or down right away.
+    pub fn set_replaygain(&mut self, replaygain: ReplayGain) {
+        self.replaygain = replaygain;
+        if self.current.is_some()
+            && let Some(song) = self.queue.first()
+        {
+            self.send(Command::Gain(replaygain.gain(song)));
+        }
+        self.invalidate_next();
+    }
+
+    pub fn eq(&self) -> &EqPresets {
+        &self.eq
+    }
+
+    pub fn set_eq(&mut self, eq: EqPresets) {
+        self.eq = eq;
+        self.apply_eq();
+    }
+
+    /// The presets to save if they were changed since they were last saved.
+    pub fn save_eq(&mut self) -> Option<SavedEq> {
+        self.eq.changed.then(|| self.eq.to_saved())
+    }
+
+    /// The equalizer preset the current song plays with.
+    pub fn eq_preset(&self) -> &str {
+        self.eq.preset_for(self.queue.first())
+    }
+
+    /// Turns a band of the preset the current song plays with up or down, by `delta` dB.
+    pub fn adjust_eq(&mut self, band: usize, delta: f32) {
+        let preset = self.eq_preset().to_string();
+        self.eq.adjust(&preset, band, delta);
+        self.apply_eq();
+    }
+
+    /// Switches to the next preset, see [`EqPresets::cycle`].
+    pub fn cycle_eq_preset(&mut self) {
+        self.eq.cycle();
+        self.apply_eq();
+    }
+
+    /// Hands the current song's equalizer settings to the engine, the next song is opened again
+    /// to pick them up as well.
+    fn apply_eq(&mut self) {
+        if self.current.is_some() {
+            self.send(Command::Equalizer(self.eq.gains(self.eq_preset())));
+        }
+        self.invalidate_next();
+    }
+
+    /// The A and B markers on the current song.
+    pub fn ab_loop(&self) -> (Option<Duration>, Option<Duration>) {
+        (self.loop_start, self.loop_end)
+    }
+
+    /// Puts the A marker at the current position, dropping a B marker that would come before it.
+    pub fn set_loop_start(&mut self) {
+        let position = self.position();
+        if self.loop_end.is_some_and(|end| end <= position) {
+            self.clear_loop();
+        }
+        self.loop_start = Some(position);
+        if let Some(end) = self.loop_end {
+            self.send(Command::Loop(Some((position, end))));
+        }
+    }
+
+    /// Puts the B marker at the current position and starts looping. Returns false if there is
+    /// no A marker before it.
+    pub fn set_loop_end(&mut self) -> bool {
+        let position = self.position();
+        match self.loop_start {
+            Some(start) if start < position => {
+                self.loop_end = Some(position);
+                self.send(Command::Loop(Some((start, position))));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn clear_loop(&mut self) {
+        self.loop_start = None;
+        self.loop_end = None;
+        self.send(Command::Loop(None));
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Changes how fast songs play, between [`MIN_SPEED`] and [`MAX_SPEED`]. The position stays
+    /// in song time, so seeking and the progress bar aren't affected. Without `keep_pitch`, faster
+    /// songs also sound higher.
+    pub fn set_speed(&mut self, speed: f32, keep_pitch: bool) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        self.send(Command::Speed {
+            speed: self.speed,
+            keep_pitch,
+        });
+    }
+
+    pub fn stop_after(&self) -> Option<usize> {
+        self.stop_after
+    }
+
+    /// Pauses once `songs` more have finished, counting the current one. The song after the
+    /// last one is still loaded, so resuming carries on with it.
+    pub fn set_stop_after(&mut self, songs: Option<usize>) {
+        self.stop_after = songs.filter(|n| *n > 0);
+        if self.stop_after == Some(1) {
+            self.invalidate_next();
+        }
+    }
+
+    pub fn repeat(&self) -> Repeat {
+        self.repeat
+    }
+
+    pub fn set_repeat(&mut self, repeat: Repeat) {
+        self.repeat = repeat;
+        self.invalidate_next();
+    }
+
+    pub fn shuffle(&self) -> Shuffle {
+        self.shuffle
+    }
+
+    /// Reorders the songs after the current one. Turning shuffle off puts them back in the order
+    /// they had before, songs added in the meantime go after them.
+    pub fn set_shuffle(&mut self, shuffle: Shuffle) {
+        let start = self.queue.len().min(1);
+        if self.shuffle == Shuffle::Off {
+            self.unshuffled = self.queue[start..].to_vec();
+        }
+        match shuffle {
+            Shuffle::Off => {
+                let mut upcoming: Vec<Song> = self.queue.drain(start..).collect();
+                for song in std::mem::take(&mut self.unshuffled) {
+                    if let Some(i) = upcoming.iter().position(|s| s.file_path == song.file_path) {
+                        self.queue.push(upcoming.remove(i));
+                    }
+                }
+                self.queue.extend(upcoming);
+            }
+            Shuffle::Tracks => self.queue[start..].shuffle(&mut rand::rng()),
+            Shuffle::Albums => shuffle_albums(&mut self.queue[start..]),
+        }
+        self.shuffle = shuffle;
+        self.invalidate_next();
+    }
+
+    pub fn seek(&mut self, pos: Duration) {
+        if self.current.is_some() {
+            self.send(Command::Seek(pos));
+            // Shown right away, even while paused and the engine isn't running
+            self.position.store(to_samples(pos), Ordering::Relaxed);
+        }
+    }
+
+    /// Puts back the queue of an earlier run, paused `position` into the first song.
+    pub fn restore(&mut self, queue: Vec<Song>, position: Duration) {
+        self.pause();
+        self.queue = queue;
+        self.start_at = Some(position);
+    }
+
+    /// How far into the current track playback is.
+    pub fn position(&self) -> Duration {
+        let samples = self.position.load(Ordering::Relaxed);
+        Duration::from_secs_f64(samples as f64 / (SAMPLE_RATE as f64 * CHANNELS as f64))
+    }
+
+    pub fn play(&self) {
+        self.sink.play();
+    }
+
+    pub fn pause(&self) {
+        self.sink.pause();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.sink.is_paused()
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.sink.volume()
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.sink.set_volume(volume);
+    }
+}
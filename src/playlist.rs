@@ -0,0 +1,293 @@
+use crate::app::Song;
+use crate::config::Config;
+use crate::event::{AppEvent, Event};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Extensions of the playlist files that can be loaded.
+pub const PLAYLIST_TYPES: [&str; 3] = ["m3u", "m3u8", "pls"];
+
+/// A song listed in a playlist, along with whatever the playlist says about it.
+#[derive(Clone, Debug, Default)]
+pub struct PlaylistEntry {
+    pub path: PathBuf,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl PlaylistEntry {
+    /// A song made up from the playlist alone, for files that aren't in the library.
+    pub fn to_song(&self, is_valid: bool) -> Song {
+        Song {
+            file_path: self.path.display().to_string(),
+            file_name: self
+                .path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            file_type: self
+                .path
+                .extension()
+                .map(|e| e.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            is_valid,
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            duration: self.duration.unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Where playlists are saved and looked for.
+pub fn playlist_dir(config: &Config) -> Option<PathBuf> {
+    match &config.library.playlist_dir {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => dirs::data_dir().map(|dir| dir.join("rrplay").join("playlists")),
+    }
+}
+
+/// The playlist files in a directory, sorted by name.
+pub fn list(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut playlists: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| PLAYLIST_TYPES.contains(&e.to_lowercase().as_str()))
+        })
+        .collect();
+    playlists.sort();
+    playlists
+}
+
+/// Reads an M3U, M3U8 or PLS playlist. Relative paths are taken from the playlist's directory.
+pub fn load(path: &Path) -> std::io::Result<Vec<PlaylistEntry>> {
+    // Plain M3U files are often not UTF-8, the odd character is better than failing
+    let content = String::from_utf8_lossy(&std::fs::read(path)?).to_string();
+    let base = path.parent().unwrap_or(Path::new(""));
+    let is_pls = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("pls"));
+    Ok(if is_pls {
+        parse_pls(&content, base)
+    } else {
+        parse_m3u(&content, base)
+    })
+}
+
+/// Turns what a playlist lists into songs and sends them back as an
+/// [`AppEvent::PlaylistLoaded`].
+///
+/// Meant to run on a blocking thread. `listed` holds the songs already found in the library and
+/// the entries that weren't, those are probed if their file exists. Entries whose file is gone
+/// stay in as invalid.
+pub fn open_songs(
+    name: String,
+    listed: Vec<Result<Song, PlaylistEntry>>,
+    sender: UnboundedSender<Event>,
+) {
+    let songs = listed
+        .into_iter()
+        .map(|listed| match listed {
+            Ok(song) => Song {
+                is_valid: song.is_valid && Path::new(&song.file_path).is_file(),
+                ..song
+            },
+            Err(entry) if entry.path.is_file() => {
+                crate::library::probe_song(&entry.path).unwrap_or_else(|_| entry.to_song(false))
+            }
+            Err(entry) => entry.to_song(false),
+        })
+        .collect();
+    let _ = sender.send(Event::App(AppEvent::PlaylistLoaded(name, songs)));
+}
+
+/// Writes songs as an extended M3U playlist.
+pub fn save(path: &Path, songs: &[Song]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut content = "#EXTM3U\n".to_string();
+    for song in songs {
+        let name = match &song.artist {
+            Some(artist) => format!("{artist} - {}", song.display_title()),
+            None => song.display_title().to_string(),
+        };
+        content.push_str(&format!(
+            "#EXTINF:{},{name}\n{}\n",
+            song.duration.as_secs(),
+            song.file_path
+        ));
+    }
+    std::fs::write(path, content)
+}
+
+fn resolve(base: &Path, location: &str) -> PathBuf {
+    let path = match location.strip_prefix("file://") {
+        Some(uri) => PathBuf::from(percent_decode(uri)),
+        None => PathBuf::from(location),
+    };
+    if path.is_absolute() {
+        path
+    } else {
+        base.join(path)
+    }
+}
+
+/// Decodes the `%XX` escapes of a URI path, keeping it as it is if they don't make valid UTF-8.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| uri.to_string())
+}
+
+/// Duration in seconds, playlists use -1 for unknown.
+fn parse_length(length: &str) -> Option<Duration> {
+    length
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| *secs > 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+fn parse_m3u(content: &str, base: &Path) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut info = PlaylistEntry::default();
+    for line in content.lines().map(|l| l.trim()) {
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:<seconds>,<artist> - <title>
+            let (length, name) = extinf.split_once(',').unwrap_or((extinf, ""));
+            info.duration = parse_length(length);
+            match name.split_once(" - ") {
+                Some((artist, title)) => {
+                    info.artist = Some(artist.trim().to_string());
+                    info.title = Some(title.trim().to_string());
+                }
+                None if !name.trim().is_empty() => info.title = Some(name.trim().to_string()),
+                None => {}
+            }
+        } else if !line.is_empty() && !line.starts_with('#') {
+            entries.push(PlaylistEntry {
+                path: resolve(base, line),
+                ..std::mem::take(&mut info)
+            });
+        }
+    }
+    entries
+}
+
+fn parse_pls(content: &str, base: &Path) -> Vec<PlaylistEntry> {
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+    for line in content.lines().map(|l| l.trim()) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let (field, number) =
+            key.split_at(key.trim_end_matches(|c: char| c.is_ascii_digit()).len());
+        let Ok(number) = number.parse::<u32>() else {
+            continue;
+        };
+        let entry = entries.entry(number).or_default();
+        match field {
+            "file" => entry.path = resolve(base, value.trim()),
+            "title" => entry.title = Some(value.trim().to_string()),
+            "length" => entry.duration = parse_length(value),
+            _ => {}
+        }
+    }
+    entries
+        .into_values()
+        .filter(|entry| !entry.path.as_os_str().is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn m3u_reads_extinf_and_resolves_relative_paths() {
+        let content = "#EXTM3U\r\n\
+            #EXTINF:215,Daft Punk - One More Time\r\n\
+            Discovery/01 One More Time.flac\r\n\
+            #EXTINF:-1,Untitled\r\n\
+            /music/untitled.mp3\r\n\
+            \r\n\
+            #EXTALB:Ignored\r\n\
+            file:///music/plain.ogg\r\n\
+            file:///music/Caf%C3%A9%20Del%20Mar.ogg\r\n";
+        let entries = parse_m3u(content, Path::new("/playlists"));
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(
+            entries[0].path,
+            PathBuf::from("/playlists/Discovery/01 One More Time.flac")
+        );
+        assert_eq!(entries[0].artist.as_deref(), Some("Daft Punk"));
+        assert_eq!(entries[0].title.as_deref(), Some("One More Time"));
+        assert_eq!(entries[0].duration, Some(Duration::from_secs(215)));
+
+        assert_eq!(entries[1].path, PathBuf::from("/music/untitled.mp3"));
+        assert_eq!(entries[1].artist, None);
+        assert_eq!(entries[1].title.as_deref(), Some("Untitled"));
+        assert_eq!(entries[1].duration, None);
+
+        // Info lines only apply to the entry right after them
+        assert_eq!(entries[2].path, PathBuf::from("/music/plain.ogg"));
+        assert_eq!(entries[2].title, None);
+
+        assert_eq!(entries[3].path, PathBuf::from("/music/Café Del Mar.ogg"));
+    }
+
+    #[test]
+    fn pls_orders_entries_by_number() {
+        let content = "[playlist]\r\n\
+            File10=ten.mp3\r\n\
+            Title10=Ten\r\n\
+            File2=/music/two.mp3\r\n\
+            Length2=61\r\n\
+            File1=one.mp3\r\n\
+            Title3=No file\r\n\
+            NumberOfEntries=3\r\n\
+            Version=2\r\n";
+        let entries = parse_pls(content, Path::new("/playlists"));
+
+        let paths: Vec<&Path> = entries.iter().map(|e| e.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("/playlists/one.mp3"),
+                Path::new("/music/two.mp3"),
+                Path::new("/playlists/ten.mp3"),
+            ]
+        );
+        assert_eq!(entries[1].duration, Some(Duration::from_secs(61)));
+        assert_eq!(entries[2].title.as_deref(), Some("Ten"));
+    }
+}
@@ -0,0 +1,40 @@
+use crate::app::{SearchBy, Song};
+use crate::persist::{read_json, write_json_atomic};
+use crate::player::Repeat;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Playback settings changed from within the app, remembered between runs.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub repeat: Repeat,
+    /// Left out when `playback.restore_session` is off.
+    pub session: Option<Session>,
+}
+
+/// What was playing when rrplay was last closed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub queue: Vec<Song>,
+    /// How far into the first song of the queue playback was.
+    pub position: Duration,
+    pub volume: f32,
+    pub search_by: SearchBy,
+}
+
+impl State {
+    pub fn state_file() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rrplay").join("state.json"))
+    }
+
+    /// Reads the state from disk, falling back to the defaults if it is missing or unreadable.
+    pub fn load() -> Self {
+        read_json(State::state_file()).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        write_json_atomic(State::state_file(), self)
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+
+/// A sample for each of the two channels.
+pub type Frame = [f32; 2];
+
+/// Length of the pieces the audio is cut into when keeping the pitch, about 23 ms.
+const WINDOW: usize = 1024;
+/// Pieces overlap by half.
+const HOP: usize = WINDOW / 2;
+/// How far a piece may be moved to line up with the one before it.
+const TOLERANCE: usize = 256;
+/// Only every few frames are compared when lining pieces up, which is plenty for finding the
+/// best fit.
+const COMPARE_STEP: usize = 4;
+
+/// Plays audio faster or slower.
+///
+/// Frames go in with [`Stretcher::push`] and come out with [`Stretcher::pop`]. Without keeping
+/// the pitch the audio is simply resampled. Keeping it cuts the audio into overlapping pieces and
+/// puts them closer together or further apart (WSOLA), each piece moved slightly to continue the
+/// waveform of the last one.
+pub struct Stretcher {
+    speed: f32,
+    keep_pitch: bool,
+    output: VecDeque<Frame>,
+    /// Resampling: the frames being interpolated between, and how far between them.
+    previous: Frame,
+    current: Frame,
+    phase: f32,
+    /// Time stretching: frames not yet used, and the position of `input[0]` in the audio.
+    input: VecDeque<Frame>,
+    input_start: usize,
+    /// Where the next piece would start without lining it up.
+    target: f64,
+    /// Second half of the last piece, already faded out, to be added to the next one.
+    tail: Vec<Frame>,
+    /// What followed the last piece in the input, the next piece should look like it.
+    natural: Vec<f32>,
+    window: Vec<f32>,
+}
+
+impl Default for Stretcher {
+    fn default() -> Self {
+        Stretcher::new()
+    }
+}
+
+impl Stretcher {
+    pub fn new() -> Self {
+        Stretcher {
+            speed: 1.0,
+            keep_pitch: true,
+            output: VecDeque::new(),
+            previous: [0.0; 2],
+            current: [0.0; 2],
+            phase: 0.0,
+            input: VecDeque::new(),
+            input_start: 0,
+            target: 0.0,
+            tail: vec![[0.0; 2]; HOP],
+            natural: Vec::new(),
+            window: (0..WINDOW)
+                .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / WINDOW as f32).cos())
+                .collect(),
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f32, keep_pitch: bool) {
+        if speed == self.speed && keep_pitch == self.keep_pitch {
+            return;
+        }
+        // Switching between stretching and resampling starts over, changing the speed of either
+        // carries on seamlessly
+        if keep_pitch != self.keep_pitch || speed == 1.0 || self.speed == 1.0 {
+            self.reset();
+        }
+        self.speed = speed;
+        self.keep_pitch = keep_pitch;
+    }
+
+    /// Drops everything buffered, for when the audio jumps somewhere else.
+    pub fn reset(&mut self) {
+        self.output.clear();
+        self.previous = [0.0; 2];
+        self.current = [0.0; 2];
+        self.phase = 0.0;
+        self.input.clear();
+        self.input_start = 0;
+        self.target = 0.0;
+        self.tail = vec![[0.0; 2]; HOP];
+        self.natural.clear();
+    }
+
+    pub fn push(&mut self, frame: Frame) {
+        if self.speed == 1.0 {
+            self.output.push_back(frame);
+        } else if self.keep_pitch {
+            self.input.push_back(frame);
+            if self.input_start + self.input.len() >= self.target as usize + TOLERANCE + WINDOW {
+                self.stretch();
+            }
+        } else {
+            self.previous = self.current;
+            self.current = frame;
+            while self.phase < 1.0 {
+                let lerp = |a: f32, b: f32| a + (b - a) * self.phase;
+                self.output.push_back([
+                    lerp(self.previous[0], self.current[0]),
+                    lerp(self.previous[1], self.current[1]),
+                ]);
+                self.phase += self.speed;
+            }
+            self.phase -= 1.0;
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Frame> {
+        self.output.pop_front()
+    }
+
+    /// Overlaps the next piece with the last one, putting out [`HOP`] frames.
+    fn stretch(&mut self) {
+        let target = self.target as usize - self.input_start;
+        let start = self.best_start(target);
+        for (n, tail) in self.tail.iter().enumerate() {
+            let frame = self.input[start + n];
+            let w = self.window[n];
+            self.output
+                .push_back([tail[0] + frame[0] * w, tail[1] + frame[1] * w]);
+        }
+        self.tail = (HOP..WINDOW)
+            .map(|n| {
+                let frame = self.input[start + n];
+                [frame[0] * self.window[n], frame[1] * self.window[n]]
+            })
+            .collect();
+        self.natural = (HOP..WINDOW)
+            .step_by(COMPARE_STEP)
+            .map(|n| self.input[start + n][0] + self.input[start + n][1])
+            .collect();
+
+        self.target += HOP as f64 * self.speed as f64;
+        // Frames before the earliest start of the next piece aren't needed anymore
+        let keep_from = (self.target as usize).saturating_sub(TOLERANCE);
+        let drop = keep_from
+            .saturating_sub(self.input_start)
+            .min(self.input.len());
+        self.input.drain(..drop);
+        self.input_start += drop;
+    }
+
+    /// Where around `target` in the input the next piece fits the last one best.
+    fn best_start(&self, target: usize) -> usize {
+        if self.natural.is_empty() {
+            return target;
+        }
+        let earliest = target.saturating_sub(TOLERANCE);
+        let mut best = (target, f32::MIN);
+        for start in (earliest..=target + TOLERANCE).step_by(2) {
+            let mut correlation = 0.0;
+            let mut energy = 0.0;
+            for (i, natural) in self.natural.iter().enumerate() {
+                let frame = self.input[start + i * COMPARE_STEP];
+                let sample = frame[0] + frame[1];
+                correlation += sample * natural;
+                energy += sample * sample;
+            }
+            let score = correlation / energy.sqrt().max(f32::EPSILON);
+            if score > best.1 {
+                best = (start, score);
+            }
+        }
+        best.0
+    }
+}
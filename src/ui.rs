@@ -1,4 +1,7 @@
 use crate::app::*;
+use crate::eq::{BANDS, MAX_GAIN};
+use crate::player::{Repeat, ReplayGain, Shuffle};
+use crate::widgets::PopupNotif;
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Rect},
@@ -33,6 +36,110 @@ fn generate_label(seconds: u64) -> String {
     }
 }
 
+fn or_na(tag: &Option<String>) -> String {
+    tag.clone().unwrap_or_else(|| "N/A".to_string())
+}
+
+/// Track number, prefixed by the disc number for multi-disc albums, e.g. `2-07`.
+fn track_label(song: &Song) -> String {
+    match (song.disc_number, song.track_number) {
+        (Some(disc), Some(track)) if song.disc_total.unwrap_or(disc) > 1 => {
+            format!("{disc}-{track:02}")
+        }
+        (_, Some(track)) => format!("{track:02}"),
+        _ => "".to_string(),
+    }
+}
+
+/// Bit depth and sample rate for lossless files, bitrate otherwise, e.g. `24/96` or `320k`.
+fn format_label(song: &Song) -> String {
+    match (song.bit_depth, song.sample_rate, song.bitrate) {
+        (Some(depth), Some(rate), _) => format!("{depth}/{}", rate as f64 / 1000.0),
+        (_, _, Some(bitrate)) => format!("{bitrate}k"),
+        _ => song.file_type.clone(),
+    }
+}
+
+/// Splits the screen into the song table above and the bottom bar.
+fn main_layout(area: Rect) -> std::rc::Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(99), Constraint::Length(3)])
+        .split(area)
+}
+
+/// Parts of the bottom bar.
+struct BottomLayout {
+    status: Rect,
+    /// Time left on the sleep timer, only there while one is set.
+    timer: Option<Rect>,
+    /// The progress bar or input.
+    main: Rect,
+    volume: Rect,
+}
+
+/// Splits the bottom bar, making room for the sleep timer next to the status if it's set.
+fn bottom_layout(app: &App, area: Rect) -> BottomLayout {
+    let bottom = main_layout(area)[1];
+    if timer_label(app).is_none() {
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![
+                Constraint::Percentage(18),
+                Constraint::Percentage(72),
+                Constraint::Percentage(10),
+            ])
+            .split(bottom);
+        BottomLayout {
+            status: layout[0],
+            timer: None,
+            main: layout[1],
+            volume: layout[2],
+        }
+    } else {
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![
+                Constraint::Percentage(18),
+                Constraint::Percentage(12),
+                Constraint::Percentage(60),
+                Constraint::Percentage(10),
+            ])
+            .split(bottom);
+        BottomLayout {
+            status: layout[0],
+            timer: Some(layout[1]),
+            main: layout[2],
+            volume: layout[3],
+        }
+    }
+}
+
+/// Where the bar of the progress gauge is drawn, inside its border.
+pub fn gauge_area(app: &App, area: Rect) -> Rect {
+    Block::bordered().inner(bottom_layout(app, area).main)
+}
+
+/// Title and text of the sleep timer box, `None` when there's nothing to stop playback.
+fn timer_label(app: &App) -> Option<(&'static str, String)> {
+    let songs = |count: usize| match count {
+        1 => "This song".to_string(),
+        _ => format!("{count} songs"),
+    };
+    if app.sleep.is_some() {
+        let text = match (app.sleep_remaining(), app.player.stop_after()) {
+            (Some(remaining), _) => generate_label(remaining.as_secs()),
+            (None, Some(count)) => songs(count),
+            (None, None) => return None,
+        };
+        Some(("Sleep", text))
+    } else {
+        app.player
+            .stop_after()
+            .map(|count| ("Stop after", songs(count)))
+    }
+}
+
 impl Widget for &App {
     // Renders the user interface widgets.
     // This is where you add new widgets.
@@ -41,39 +148,29 @@ impl Widget for &App {
     // - https://github.com/ratatui/ratatui/tree/master/examples
 
     fn render(self, area: Rect, buf: &mut Buffer) {
-        // Color for the focused window
-        // const focus_color: Color = Color::Blue;
+        let colors = &self.config.ui.colors;
 
-        let layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Length(99), Constraint::Length(3)])
-            .split(area);
+        let layout = main_layout(area);
 
+        // Columns of the song table: header, the field it searches by and its width
+        let columns = [
+            ("[1] Path", Some(SearchBy::FilePath), 10),
+            ("#", None, 4),
+            ("[2] Title", Some(SearchBy::Title), 20),
+            ("[3] Artist", Some(SearchBy::Artist), 11),
+            ("[4] Album", Some(SearchBy::Album), 11),
+            ("Album artist", None, 9),
+            ("Composer", None, 8),
+            ("Year", None, 4),
+            ("[5] Genre", Some(SearchBy::Genre), 7),
+            ("Format", None, 9),
+            ("Length", None, 7),
+        ];
         let top_layout = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints(vec![
-                // File path
-                Constraint::Percentage(15),
-                // Title
-                Constraint::Percentage(40),
-                // Artist
-                Constraint::Percentage(15),
-                // Album
-                Constraint::Percentage(15),
-                // Genre
-                Constraint::Percentage(10),
-                // Duration
-                Constraint::Percentage(5),
-            ])
+            .constraints(columns.iter().map(|c| Constraint::Percentage(c.2)))
             .split(layout[0]);
-        let bottom_layout = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(vec![
-                Constraint::Percentage(18),
-                Constraint::Percentage(72),
-                Constraint::Percentage(10),
-            ])
-            .split(layout[1]);
+        let bottom_layout = bottom_layout(self, area);
 
         let title_block = Block::bordered()
             .title({
@@ -81,129 +178,97 @@ impl Widget for &App {
                     "Search results"
                 } else if self.mode == Mode::Sitback {
                     "Queue"
+                } else if self.mode == Mode::History {
+                    "History"
+                } else if self.mode == Mode::Playlists {
+                    "Playlists"
+                } else if self.mode == Mode::Equalizer {
+                    "Equalizer"
+                } else if self.mode == Mode::Bookmarks {
+                    "Bookmarks"
+                } else if matches!(
+                    self.mode,
+                    Mode::SavePlaylist | Mode::NameBookmark | Mode::Command
+                ) {
+                    "Queue"
                 } else {
                     "Undefined"
                 }
             })
+            .title_bottom({
+                // Comments don't fit in a column, show the one of the selected song instead
+                let selected = self
+                    .search_results
+                    .len()
+                    .checked_sub(1 + self.select_index)
+                    .and_then(|i| self.search_results.get(i));
+                match selected.and_then(|song| song.comment.as_deref()) {
+                    Some(comment) if self.mode == Mode::Select => format!(" {comment} "),
+                    _ => "".to_string(),
+                }
+            })
             .title_alignment(Alignment::Center)
             .border_type(BorderType::Plain)
             .border_style({
                 if self.mode == Mode::Select {
-                    Style::new().yellow()
+                    Style::new().fg(colors.accent)
                 } else {
                     Style::new()
                 }
             });
         let borderless_block = Block::new().borders(Borders::NONE);
         let array_test: Vec<Vec<Line<'_>>> = {
-            let mut tmp_results: Vec<Vec<Line<'_>>> = vec![
-                vec![
-                    "".into(),
-                    Span::styled(
-                        "[1] Path",
-                        Style::default()
-                            .fg(
-                                if self.search_by == SearchBy::FilePath
-                                    && (self.mode == Mode::Search || self.mode == Mode::Select)
-                                {
-                                    Color::Yellow
-                                } else {
-                                    Color::White
-                                },
-                            )
-                            .add_modifier(Modifier::BOLD),
-                    )
-                    .into(),
-                ],
-                vec![
-                    "".into(),
-                    Span::styled(
-                        "[2] Title",
-                        Style::default()
-                            .fg(
-                                if self.search_by == SearchBy::Title
-                                    && (self.mode == Mode::Search || self.mode == Mode::Select)
-                                {
-                                    Color::Yellow
-                                } else {
-                                    Color::White
-                                },
-                            )
-                            .add_modifier(Modifier::BOLD),
-                    )
-                    .into(),
-                ],
-                vec![
-                    "".into(),
-                    Span::styled(
-                        "[3] Artist",
-                        Style::default()
-                            .fg(
-                                if self.search_by == SearchBy::Artist
-                                    && (self.mode == Mode::Search || self.mode == Mode::Select)
-                                {
-                                    Color::Yellow
-                                } else {
-                                    Color::White
-                                },
-                            )
-                            .add_modifier(Modifier::BOLD),
-                    )
-                    .into(),
-                ],
-                vec![
-                    "".into(),
-                    Span::styled(
-                        "[4] Album",
-                        Style::default()
-                            .fg(
-                                if self.search_by == SearchBy::Album
-                                    && (self.mode == Mode::Search || self.mode == Mode::Select)
-                                {
-                                    Color::Yellow
-                                } else {
-                                    Color::White
-                                },
-                            )
-                            .add_modifier(Modifier::BOLD),
-                    )
-                    .into(),
-                ],
-                vec![
-                    "".into(),
-                    Span::styled(
-                        "[5] Genre",
-                        Style::default()
-                            .fg(
-                                if self.search_by == SearchBy::Genre
-                                    && (self.mode == Mode::Search || self.mode == Mode::Select)
-                                {
-                                    Color::Yellow
-                                } else {
-                                    Color::White
-                                },
-                            )
-                            .add_modifier(Modifier::BOLD),
-                    )
-                    .into(),
-                ],
-                vec![
-                    "".into(),
-                    Span::styled("Length", Style::default().add_modifier(Modifier::BOLD)).into(),
-                ],
-            ];
-            let mut to_iter: Vec<Song> = if self.mode == Mode::Sitback {
-                self.queue.clone()
+            let mut tmp_results: Vec<Vec<Line<'_>>> = columns
+                .iter()
+                .map(|(label, search_by, _)| {
+                    vec![
+                        "".into(),
+                        Span::styled(
+                            *label,
+                            Style::default()
+                                .fg(
+                                    if search_by.as_ref() == Some(&self.search_by)
+                                        && (self.mode == Mode::Search || self.mode == Mode::Select)
+                                    {
+                                        colors.accent
+                                    } else {
+                                        Color::White
+                                    },
+                                )
+                                .add_modifier(Modifier::BOLD),
+                        )
+                        .into(),
+                    ]
+                })
+                .collect();
+            let mut to_iter: Vec<Song> = if matches!(
+                self.mode,
+                Mode::Sitback | Mode::SavePlaylist | Mode::NameBookmark | Mode::Command
+            ) {
+                self.player.queue.clone()
+            } else if self.mode == Mode::History {
+                self.player.history.iter().cloned().collect()
+            } else if matches!(
+                self.mode,
+                Mode::Playlists | Mode::Equalizer | Mode::Bookmarks
+            ) {
+                Vec::new()
             } else {
                 self.search_results.clone()
             };
-            to_iter.truncate({
-                if self.terminal_size.1 > 10 {
-                    (self.terminal_size.1 - 6).into()
-                } else {
-                    self.terminal_size.1.into()
-                }
-            });
+            let rows: usize = if self.terminal_size.1 > 10 {
+                (self.terminal_size.1 - 6).into()
+            } else {
+                self.terminal_size.1.into()
+            };
+            // Scroll the queue far enough for the cursor to stay in view
+            let first = if self.mode == Mode::Sitback {
+                (self.queue_index + 1).saturating_sub(rows)
+            } else {
+                0
+            };
+            to_iter.drain(..first.min(to_iter.len()));
+            to_iter.truncate(rows);
 
             // TODO: Use a scrollable table instead of a paragraph
             for (i, song) in to_iter.iter().enumerate() {
@@ -217,11 +282,16 @@ impl Widget for &App {
                                         cut_str.push_str(&song.file_path);
                                         cut_str
                                     }
-                                    1 => song.title.clone(),
-                                    2 => song.artist.clone(),
-                                    3 => song.album.clone(),
-                                    4 => song.genre.clone(),
-                                    5 => generate_label(song.duration.as_secs()),
+                                    1 => track_label(song),
+                                    2 => song.display_title().to_string(),
+                                    3 => or_na(&song.artist),
+                                    4 => or_na(&song.album),
+                                    5 => or_na(&song.album_artist),
+                                    6 => or_na(&song.composer),
+                                    7 => song.year.map_or("N/A".to_string(), |y| y.to_string()),
+                                    8 => or_na(&song.genre),
+                                    9 => format_label(song),
+                                    10 => generate_label(song.duration.as_secs()),
                                     _ => "Undefined".to_string(),
                                 }
                             },
@@ -235,8 +305,16 @@ impl Widget for &App {
                                     {
                                         Modifier::BOLD
                                     } else if self.mode == Mode::Sitback {
-                                        if i == 0 {
-                                            Modifier::UNDERLINED
+                                        let index = first + i;
+                                        let cursor = if index == self.queue_index {
+                                            Modifier::BOLD
+                                        } else {
+                                            Modifier::empty()
+                                        };
+                                        if index == 0 {
+                                            Modifier::UNDERLINED | cursor
+                                        } else if index == self.queue_index {
+                                            cursor
                                         } else {
                                             Modifier::DIM
                                         }
@@ -246,9 +324,9 @@ impl Widget for &App {
                                 })
                                 .fg({
                                     if song.is_valid {
-                                        Color::Green
+                                        colors.valid
                                     } else {
-                                        Color::Red
+                                        colors.invalid
                                     }
                                 }),
                         )
@@ -258,11 +336,121 @@ impl Widget for &App {
             }
             tmp_results.clone()
         };
-        for (i, n) in array_test.iter().enumerate() {
-            Paragraph::new(n.clone())
+        if self.mode == Mode::Equalizer {
+            let eq = self.player.eq();
+            let preset = self.player.eq_preset();
+            let picked = if eq.chosen.is_some() {
+                preset.to_string()
+            } else {
+                format!("{preset} (by genre)")
+            };
+            let mut lines: Vec<Line<'_>> = vec![
+                "".into(),
+                Span::styled(
+                    format!("Preset: {picked}"),
+                    Style::new().add_modifier(Modifier::BOLD),
+                )
+                .into(),
+                "".into(),
+            ];
+            let half = MAX_GAIN as usize;
+            for (i, (freq, gain)) in BANDS.iter().zip(eq.gains(preset)).enumerate() {
+                // A bar growing left or right from the middle, one cell per dB
+                let cells = (gain.abs().round() as usize).min(half);
+                let (left, right) = if gain < 0.0 {
+                    (
+                        format!("{}{}", " ".repeat(half - cells), "█".repeat(cells)),
+                        " ".repeat(half),
+                    )
+                } else {
+                    (
+                        " ".repeat(half),
+                        format!("{}{}", "█".repeat(cells), " ".repeat(half - cells)),
+                    )
+                };
+                let freq = if *freq >= 1000.0 {
+                    format!("{}k", freq / 1000.0)
+                } else {
+                    format!("{freq}")
+                };
+                lines.push(
+                    Span::styled(
+                        format!("{freq:>4} Hz {left}│{right} {gain:>+5.1} dB"),
+                        if i == self.eq_band {
+                            Style::new().fg(colors.accent).add_modifier(Modifier::BOLD)
+                        } else {
+                            Style::new().add_modifier(Modifier::DIM)
+                        },
+                    )
+                    .into(),
+                );
+            }
+            Paragraph::new(lines)
                 .block(borderless_block.clone())
                 .centered()
-                .render(top_layout[i], buf);
+                .render(layout[0], buf);
+        } else if self.mode == Mode::Bookmarks {
+            let bookmarks = self.current_bookmarks();
+            let lines: Vec<Line<'_>> = if self.player.queue.is_empty() {
+                vec!["".into(), "Nothing is playing".into()]
+            } else if bookmarks.is_empty() {
+                vec!["".into(), "No bookmarks in this song yet".into()]
+            } else {
+                std::iter::once("".into())
+                    .chain(bookmarks.iter().enumerate().map(|(i, bookmark)| {
+                        Span::styled(
+                            format!(
+                                "{}  {}",
+                                generate_label(bookmark.position.as_secs()),
+                                bookmark.name
+                            ),
+                            if i == self.bookmark_index {
+                                Style::new().fg(colors.accent).add_modifier(Modifier::BOLD)
+                            } else {
+                                Style::new().add_modifier(Modifier::DIM)
+                            },
+                        )
+                        .into()
+                    }))
+                    .collect()
+            };
+            Paragraph::new(lines)
+                .block(borderless_block.clone())
+                .centered()
+                .render(layout[0], buf);
+        } else if self.mode == Mode::Playlists {
+            let lines: Vec<Line<'_>> = if self.playlists.is_empty() {
+                vec!["".into(), "No playlists yet".into()]
+            } else {
+                std::iter::once("".into())
+                    .chain(self.playlists.iter().enumerate().map(|(i, path)| {
+                        let name = path
+                            .file_name()
+                            .map(|f| f.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        Span::styled(
+                            name,
+                            if i == self.playlist_index {
+                                Style::new().fg(colors.accent).add_modifier(Modifier::BOLD)
+                            } else {
+                                Style::new().add_modifier(Modifier::DIM)
+                            },
+                        )
+                        .into()
+                    }))
+                    .collect()
+            };
+            Paragraph::new(lines)
+                .block(borderless_block.clone())
+                .centered()
+                .render(layout[0], buf);
+        } else {
+            for (i, n) in array_test.iter().enumerate() {
+                Paragraph::new(n.clone())
+                    .block(borderless_block.clone())
+                    .centered()
+                    .render(top_layout[i], buf);
+            }
         }
 
         Paragraph::new("")
@@ -270,61 +458,99 @@ impl Widget for &App {
             .centered()
             .render(layout[0], buf);
 
+        // The loop markers go where the gauge has room for them
+        let ab_loop = match self.player.ab_loop() {
+            (Some(start), Some(end)) => format!(
+                "Loop {} - {}",
+                generate_label(start.as_secs()),
+                generate_label(end.as_secs())
+            ),
+            (Some(start), None) => format!("A {}", generate_label(start.as_secs())),
+            _ => "".to_string(),
+        };
         let mode_block = Block::bordered()
             .title(if self.mode == Mode::Search {
                 "Query"
-            } else if self.mode == Mode::Sitback {
-                ""
+            } else if self.shows_gauge() {
+                &ab_loop
             } else if self.mode == Mode::Select {
                 "Query"
+            } else if self.mode == Mode::SavePlaylist {
+                "Playlist name"
+            } else if self.mode == Mode::NameBookmark {
+                "Bookmark name"
+            } else if self.mode == Mode::Command {
+                "Command"
             } else {
                 "Undefined"
             })
             .title_alignment(Alignment::Center)
             .border_type(BorderType::Plain)
-            .border_style(if self.mode == Mode::Search {
-                Style::new().yellow()
-            } else {
-                Style::new()
-            });
+            .border_style(
+                if matches!(
+                    self.mode,
+                    Mode::Search | Mode::SavePlaylist | Mode::NameBookmark | Mode::Command
+                ) {
+                    Style::new().fg(colors.accent)
+                } else {
+                    Style::new()
+                },
+            );
 
         let status_volume_block = Block::bordered()
             .title("Volume")
             .title_alignment(Alignment::Center)
             .border_type(BorderType::Plain);
 
+        let modes: Vec<String> = [
+            (self.player.speed() != 1.0).then(|| format!("{}x", self.player.speed())),
+            (self.player.repeat() != Repeat::Off).then(|| self.player.repeat().label().to_string()),
+            (self.player.shuffle() != Shuffle::Off)
+                .then(|| self.player.shuffle().label().to_string()),
+            self.player.crossfade().map(|_| "Crossfade".to_string()),
+            (self.player.replaygain() != ReplayGain::Off)
+                .then(|| self.player.replaygain().label().to_string()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
         let status_playing_block = Block::bordered()
             .title("Status")
+            .title_bottom(if modes.is_empty() {
+                "".to_string()
+            } else {
+                format!(" {} ", modes.join(", "))
+            })
             .title_alignment(Alignment::Center)
             .border_type(BorderType::Plain);
 
-        if self.mode == Mode::Sitback {
-            if !self.queue.is_empty() {
+        if self.shows_gauge() {
+            if !self.player.queue.is_empty() {
                 let label = Span::styled(
-                    generate_label(self.sink.get_pos().as_secs()),
+                    generate_label(self.player.position().as_secs()),
                     Style::new().italic().bold().fg(Color::DarkGray),
                 );
                 Gauge::default()
                     .block(mode_block)
-                    .gauge_style(if self.sink.is_paused() {
-                        Style::new().red()
+                    .gauge_style(if self.player.is_paused() {
+                        Style::new().fg(colors.paused)
                     } else {
-                        Style::new().green()
+                        Style::new().fg(colors.playing)
                     })
                     .ratio({
-                        let ratio = self.sink.get_pos().as_secs_f64()
-                            / self.queue[0].duration.as_secs_f64();
+                        let ratio = self.player.position().as_secs_f64()
+                            / self.player.queue[0].duration.as_secs_f64();
                         clamp(0.0, 1.0, ratio)
                     })
                     .label(label)
-                    .render(bottom_layout[1], buf);
+                    .render(bottom_layout.main, buf);
             } else {
                 Paragraph::new("Empty queue")
                     .block(mode_block)
                     .fg(Color::White)
                     .bg(Color::Black)
                     .centered()
-                    .render(bottom_layout[1], buf);
+                    .render(bottom_layout.main, buf);
             }
         } else if self.mode == Mode::Search || self.mode == Mode::Select {
             Paragraph::new(self.query.clone())
@@ -332,19 +558,40 @@ impl Widget for &App {
                 .fg(Color::White)
                 .bg(Color::Black)
                 .centered()
-                .render(bottom_layout[1], buf);
+                .render(bottom_layout.main, buf);
+        } else if self.mode == Mode::SavePlaylist {
+            Paragraph::new(self.playlist_name.clone())
+                .block(mode_block)
+                .fg(Color::White)
+                .bg(Color::Black)
+                .centered()
+                .render(bottom_layout.main, buf);
+        } else if self.mode == Mode::NameBookmark {
+            Paragraph::new(self.bookmark_name.clone())
+                .block(mode_block)
+                .fg(Color::White)
+                .bg(Color::Black)
+                .centered()
+                .render(bottom_layout.main, buf);
+        } else if self.mode == Mode::Command {
+            Paragraph::new(format!(":{}", self.command))
+                .block(mode_block)
+                .fg(Color::White)
+                .bg(Color::Black)
+                .render(bottom_layout.main, buf);
         };
-        let volume_paragraph = Paragraph::new(((self.sink.volume() * 100.0) as usize).to_string())
-            .block(status_volume_block)
-            .fg(Color::White)
-            .bg(Color::Black)
-            .centered();
-        volume_paragraph.render(bottom_layout[2], buf);
+        let volume_paragraph =
+            Paragraph::new(((self.player.volume() * 100.0) as usize).to_string())
+                .block(status_volume_block)
+                .fg(Color::White)
+                .bg(Color::Black)
+                .centered();
+        volume_paragraph.render(bottom_layout.volume, buf);
 
         Paragraph::new({
-            if self.queue.is_empty() {
+            if self.player.queue.is_empty() {
                 "N/A"
-            } else if self.sink.is_paused() {
+            } else if self.player.is_paused() {
                 "Paused"
             } else {
                 "Playing"
@@ -354,10 +601,45 @@ impl Widget for &App {
         .fg(Color::White)
         .bg(Color::Black)
         .centered()
-        .render(bottom_layout[0], buf);
+        .render(bottom_layout.status, buf);
+        if let (Some(timer_area), Some((title, text))) = (bottom_layout.timer, timer_label(self)) {
+            Paragraph::new(text)
+                .block(
+                    Block::bordered()
+                        .title(title)
+                        .title_alignment(Alignment::Center)
+                        .border_type(BorderType::Plain),
+                )
+                .fg(Color::White)
+                .bg(Color::Black)
+                .centered()
+                .render(timer_area, buf);
+        }
         for notif in self.popup_notif.clone() {
             notif.render(area, buf);
         }
+        if let Some(scan) = &self.scan {
+            PopupNotif {
+                title: "Scanning library".to_string(),
+                message: vec![
+                    (format!("Found: {}", scan.progress.found), Color::White),
+                    (format!("Cached: {}", scan.progress.cached), Color::White),
+                    (format!("Probed: {}", scan.progress.probed), Color::White),
+                    (
+                        format!("Failed: {}", scan.progress.failed),
+                        if scan.progress.failed > 0 {
+                            Color::Red
+                        } else {
+                            Color::White
+                        },
+                    ),
+                ],
+                border_color: Color::Blue,
+                duration_ticks: Some(1),
+                index: self.popup_notif.len() + 1,
+            }
+            .render(area, buf);
+        }
         if let Some(mp) = self.popup_manual.clone() {
             mp.render(area, buf);
         }
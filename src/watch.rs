@@ -0,0 +1,82 @@
+use crate::event::{AppEvent, Event};
+use crate::library::SourceRules;
+use notify::event::{AccessKind, AccessMode, EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// How long the sources have to stay quiet before pending changes are applied. Copying an album
+/// fires a burst of events, this folds it into a single update.
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// Watches the sources for files being created, modified, removed or renamed.
+pub struct SourceWatcher {
+    _watcher: RecommendedWatcher,
+    /// Paths touched since the last update was applied.
+    pending: HashSet<PathBuf>,
+    last_event: Option<Instant>,
+}
+
+impl SourceWatcher {
+    /// Starts watching every source recursively. Touched paths are sent back as
+    /// [`AppEvent::FsChanged`].
+    ///
+    /// Sources that can't be watched, e.g. once the system's limit of watches is reached, are
+    /// left out and returned along with the error. The others are still watched.
+    pub fn new(
+        sources: &[SourceRules],
+        sender: UnboundedSender<Event>,
+    ) -> notify::Result<(Self, Vec<(PathBuf, notify::Error)>)> {
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                if let Ok(event) = res
+                    && is_relevant(&event.kind)
+                    && !event.paths.is_empty()
+                {
+                    let _ = sender.send(Event::App(AppEvent::FsChanged(event.paths)));
+                }
+            })?;
+        let mut failures = Vec::new();
+        for source in sources {
+            if let Err(err) = watcher.watch(&source.root, RecursiveMode::Recursive) {
+                failures.push((source.root.clone(), err));
+            }
+        }
+        let watcher = SourceWatcher {
+            _watcher: watcher,
+            pending: HashSet::new(),
+            last_event: None,
+        };
+        Ok((watcher, failures))
+    }
+
+    pub fn push(&mut self, paths: Vec<PathBuf>) {
+        self.pending.extend(paths);
+        self.last_event = Some(Instant::now());
+    }
+
+    /// Takes the pending paths once no new events arrived for [`SETTLE_TIME`].
+    pub fn take_settled(&mut self) -> Option<Vec<PathBuf>> {
+        match self.last_event {
+            Some(last) if last.elapsed() >= SETTLE_TIME && !self.pending.is_empty() => {
+                self.last_event = None;
+                Some(self.pending.drain().collect())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Whether an event can change what's in the library. Plain reads and metadata changes such as
+/// access times are ignored, a finished write is not.
+fn is_relevant(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Create(_) | EventKind::Remove(_) | EventKind::Any => true,
+        EventKind::Modify(ModifyKind::Metadata(_)) => false,
+        EventKind::Modify(_) => true,
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+        EventKind::Access(_) | EventKind::Other => false,
+    }
+}
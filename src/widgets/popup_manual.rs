@@ -11,6 +11,8 @@ pub struct PopupManual {
     pub title: String,
     pub bottom_hint: String,
     pub border_color: Color,
+    /// Lines scrolled past, for messages longer than the popup.
+    pub scroll: u16,
 }
 impl Default for PopupManual {
     fn default() -> Self {
@@ -19,24 +21,45 @@ impl Default for PopupManual {
             message: vec![("This is a manual popup".into(), Color::White)],
             bottom_hint: "Press <Esc> to close the window".into(),
             border_color: Color::Red,
+            scroll: 0,
         }
     }
 }
 
+impl PopupManual {
+    /// Where the popup goes on a screen of the given size.
+    fn area(screen: Rect) -> Rect {
+        Rect {
+            x: screen.width / 4,
+            y: screen.height / 3,
+            width: screen.width / 2,
+            height: screen.height / 3,
+        }
+    }
+
+    /// How far the message can be scrolled on a screen of the given size, the last line stays
+    /// at the bottom of the popup.
+    pub fn max_scroll(&self, screen: Rect) -> u16 {
+        let visible = PopupManual::area(screen).height.saturating_sub(2) as usize;
+        self.message
+            .len()
+            .saturating_sub(visible)
+            .try_into()
+            .unwrap_or(u16::MAX)
+    }
+}
+
 impl Widget for PopupManual {
     fn render(self, area: Rect, buf: &mut Buffer) {
+        // The screen may have shrunk since the popup was scrolled
+        let scroll = self.scroll.min(self.max_scroll(area));
         let help_block = Block::new()
             .title(self.title)
             .title_style(Style::new().white().bold())
             .borders(Borders::ALL)
             .border_style(Style::new().fg(self.border_color));
 
-        let popup_area = Rect {
-            x: area.width / 4,
-            y: area.height / 3,
-            width: area.width / 2,
-            height: area.height / 3,
-        };
+        let popup_area = PopupManual::area(area);
 
         if popup_area.height < 1 {
             return;
@@ -51,6 +74,7 @@ impl Widget for PopupManual {
         Paragraph::new(text_for_render)
             .style(Style::new())
             .block(help_block)
+            .scroll((scroll, 0))
             .render(popup_area, buf);
         let mut hint_popup_area = popup_area;
         hint_popup_area.y += popup_area.height - 1;
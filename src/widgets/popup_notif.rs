@@ -26,8 +26,8 @@ impl Default for PopupNotif {
 }
 
 impl Widget for PopupNotif {
-    fn render(self, area: Rect, buf: &mut Buffer) -> () {
-        if let Some(_) = self.duration_ticks {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if self.duration_ticks.is_some() {
             let ratio = (4, 5);
             if self.index > ratio.1.into() {
                 return;